@@ -1,4 +1,7 @@
-use crate::{csp_pfb::CspPfb, cspfb, oscillator::COscillator, ospfb, ospfb2};
+use crate::{
+    cascade::CascadeChannelizer, channelizer::Channelizer, csp_pfb::CspPfb, cspfb,
+    oscillator::COscillator, ospfb, ospfb2,
+};
 
 use num::{
     complex::Complex,
@@ -8,9 +11,51 @@ use num::{
 use ndarray::{Array2, Axis, ScalarOperand};
 use rustfft::FftNum;
 use serde::Serialize;
+use std::iter::Sum;
 
 //use serde_yaml::to_writer;
 
+/// amplitude response of a two stage channelizer to a single frequency complex signal
+/// * `coarse_pfb` - coarse channelizer, e.g., [`ospfb::Analyzer`] or [`ospfb2::Analyzer`]
+/// * `csp` - fine channelizer, working on the coarse channels it selects
+/// * return value - (response of the selected coarse channels, response of the fine channels)
+pub fn ampl_resp_2stages_1freq_with<C, T>(
+    coarse_pfb: &mut C,
+    csp: &mut CspPfb<T>,
+    freq: T,
+    signal_len: usize,
+    niter: usize,
+) -> (Vec<T>, Vec<T>)
+where
+    C: Channelizer<Input = [Complex<T>], Output = Complex<T>>,
+    T: Float + FloatConst + NumAssign + FftNum + Default + ScalarOperand + Serialize,
+    Complex<T>: ScalarOperand,
+{
+    let mut osc = COscillator::new(T::zero(), freq);
+    for _i in 0..niter - 1 {
+        let mut signal = vec![Complex::<T>::default(); signal_len];
+        signal.iter_mut().for_each(|x| *x = osc.get());
+        let coarse_data = coarse_pfb.analyze(&signal);
+        let _ = csp.analyze(coarse_data.view());
+    }
+
+    let mut signal = vec![Complex::<T>::default(); signal_len];
+    signal.iter_mut().for_each(|x| *x = osc.get());
+    let coarse_data = coarse_pfb.analyze(&signal);
+    let coarse_spec = coarse_data.map(|x| x.norm_sqr()).sum_axis(Axis(1));
+
+    let coarse_resp: Vec<_> = csp
+        .coarse_ch_selected()
+        .iter()
+        .map(|&c| coarse_spec[c])
+        .collect();
+
+    let fine_data = csp.analyze(coarse_data.view());
+
+    let fine_resp = fine_data.map(|x| x.norm_sqr()).sum_axis(Axis(1)).to_vec();
+    (coarse_resp, fine_resp)
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn ampl_resp_2stages_1freq<T>(
     nch_coarse: usize,
@@ -26,13 +71,11 @@ where
     T: Float + FloatConst + NumAssign + FftNum + Default + ScalarOperand + Serialize,
     Complex<T>: ScalarOperand,
 {
-    let mut coarse_pfb =
-        ospfb::Analyzer::<Complex<T>, T>::new(nch_coarse, coeff_coarse);
+    let mut coarse_pfb = ospfb::Analyzer::<Complex<T>, T>::new(nch_coarse, coeff_coarse);
 
     //to_writer(std::fs::File::create("./coarse_pfb.yaml").unwrap(), &coarse_pfb).unwrap();
 
-    let fine_pfb =
-        cspfb::Analyzer::<Complex<T>, T>::new(nch_fine * 2, coeff_fine);
+    let fine_pfb = cspfb::Analyzer::<Complex<T>, T>::new(nch_fine * 2, coeff_fine);
 
     //to_writer(std::fs::File::create("./fine_pfb.yaml").unwrap(), &fine_pfb).unwrap();
 
@@ -42,33 +85,41 @@ where
     assert_eq!(tap_fine * nch_fine * 2, coeff_fine.len());
 
     let mut csp = CspPfb::new(selected_coarse_ch, &fine_pfb);
+    ampl_resp_2stages_1freq_with(&mut coarse_pfb, &mut csp, freq, signal_len, niter)
+}
+
+/// amplitude response of every stage of a cascaded channelizer to a single frequency complex signal
+/// * `cascade` - the cascaded channelizer
+/// * `freq` - angular frequency of the signal, in radians per sample
+/// * `signal_len` - length of the signal of each iteration
+/// * `niter` - number of iterations, only the output of the last one is used
+/// * return value - response of all output channels of each stage
+pub fn ampl_resp_cascade_1freq<T>(
+    cascade: &mut CascadeChannelizer<T>,
+    freq: T,
+    signal_len: usize,
+    niter: usize,
+) -> Vec<Vec<T>>
+where
+    T: Float + FloatConst + NumAssign + FftNum + Default + ScalarOperand + Sum,
+    Complex<T>: ScalarOperand,
+{
     let mut osc = COscillator::new(T::zero(), freq);
     for _i in 0..niter - 1 {
-        let mut signal = vec![Complex::<T>::default(); signal_len];
-        signal.iter_mut().for_each(|x| *x = osc.get());
-        let coarse_data = coarse_pfb.analyze(&signal);
-        let _ = csp.analyze(coarse_data.view());
+        let signal: Vec<_> = (0..signal_len).map(|_| osc.get()).collect();
+        let _ = cascade.analyze_stages(&signal);
     }
-
-    let mut signal = vec![Complex::<T>::default(); signal_len];
-    signal.iter_mut().for_each(|x| *x = osc.get());
-    let coarse_data = coarse_pfb.analyze(&signal);
-    let coarse_spec = coarse_data.map(|x| x.norm_sqr()).sum_axis(Axis(1));
-
-    let coarse_resp: Vec<_> = selected_coarse_ch.iter().map(|&c| coarse_spec[c]).collect();
-
-    let fine_data = csp.analyze(coarse_data.view());
-
-    let fine_resp = fine_data
-        .map(|x| x.norm_sqr())
-        .sum_axis(Axis(1))
-        .into_raw_vec();
-    (coarse_resp, fine_resp)
+    let signal: Vec<_> = (0..signal_len).map(|_| osc.get()).collect();
+    cascade
+        .analyze_stages(&signal)
+        .iter()
+        .map(|y| y.map(|x| x.norm_sqr()).sum_axis(Axis(1)).to_vec())
+        .collect()
 }
 
 #[allow(clippy::too_many_arguments)]
-pub fn ampl_resp<T>(
-    pfb: &mut ospfb::Analyzer<Complex<T>, T>,
+pub fn ampl_resp<C, T>(
+    pfb: &mut C,
     f_min: T,
     f_max: T,
     n_freq: usize,
@@ -76,13 +127,14 @@ pub fn ampl_resp<T>(
     niter: usize,
 ) -> Array2<T>
 where
+    C: Channelizer<Input = [Complex<T>], Output = Complex<T>>,
     T: Float + FloatConst + NumAssign + FftNum + Default + ScalarOperand + Serialize,
     Complex<T>: ScalarOperand,
 {
     //let mut coarse_pfb =
     //    ospfb::Analyzer::<Complex<T>, T>::new(nch_coarse, ArrayView1::from(&coeff_coarse));
     let df = (f_max - f_min) / T::from(n_freq - 1).unwrap();
-    let mut result = Array2::zeros((pfb.nch_total(), n_freq));
+    let mut result = Array2::zeros((pfb.nch(), n_freq));
 
     result
         .axis_iter_mut(Axis(1))
@@ -111,7 +163,6 @@ where
     result
 }
 
-
 #[allow(clippy::too_many_arguments)]
 pub fn ampl_resp_2stages_1freq2<T>(
     nch_coarse: usize,
@@ -127,13 +178,11 @@ where
     T: Float + FloatConst + NumAssign + FftNum + Default + ScalarOperand + Serialize,
     Complex<T>: ScalarOperand,
 {
-    let mut coarse_pfb =
-        ospfb2::Analyzer::<Complex<T>, T>::new(nch_coarse, coeff_coarse);
+    let mut coarse_pfb = ospfb2::Analyzer::<Complex<T>, T>::new(nch_coarse, coeff_coarse);
 
     //to_writer(std::fs::File::create("./coarse_pfb.yaml").unwrap(), &coarse_pfb).unwrap();
 
-    let fine_pfb =
-        cspfb::Analyzer::<Complex<T>, T>::new(nch_fine * 2, coeff_fine);
+    let fine_pfb = cspfb::Analyzer::<Complex<T>, T>::new(nch_fine * 2, coeff_fine);
 
     //to_writer(std::fs::File::create("./fine_pfb.yaml").unwrap(), &fine_pfb).unwrap();
 
@@ -143,26 +192,5 @@ where
     assert_eq!(tap_fine * nch_fine * 2, coeff_fine.len());
 
     let mut csp = CspPfb::new(selected_coarse_ch, &fine_pfb);
-    let mut osc = COscillator::new(T::zero(), freq);
-    for _i in 0..niter - 1 {
-        let mut signal = vec![Complex::<T>::default(); signal_len];
-        signal.iter_mut().for_each(|x| *x = osc.get());
-        let coarse_data = coarse_pfb.analyze(&signal);
-        let _ = csp.analyze(coarse_data.view());
-    }
-
-    let mut signal = vec![Complex::<T>::default(); signal_len];
-    signal.iter_mut().for_each(|x| *x = osc.get());
-    let coarse_data = coarse_pfb.analyze(&signal);
-    let coarse_spec = coarse_data.map(|x| x.norm_sqr()).sum_axis(Axis(1));
-
-    let coarse_resp: Vec<_> = selected_coarse_ch.iter().map(|&c| coarse_spec[c]).collect();
-
-    let fine_data = csp.analyze(coarse_data.view());
-
-    let fine_resp = fine_data
-        .map(|x| x.norm_sqr())
-        .sum_axis(Axis(1))
-        .into_raw_vec();
-    (coarse_resp, fine_resp)
+    ampl_resp_2stages_1freq_with(&mut coarse_pfb, &mut csp, freq, signal_len, niter)
 }
@@ -1,6 +1,6 @@
 //! A module containing FIR filter
 
-use serde::{Deserialize, Serialize};
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
 
 use std::{
     iter::Sum,
@@ -9,21 +9,155 @@ use std::{
 
 use num::complex::Complex;
 
-use ndarray::{parallel::prelude::*, ArrayView2, Axis};
+use ndarray::{parallel::prelude::*, s, Array2, ArrayView1, ArrayView2, ArrayViewMut2, Axis};
+
+/// The serialized form of [`BatchFilter`], which is kept the same as that of the old `Vec<Vec<_>>` based implementation
+#[derive(Deserialize)]
+struct BatchFilterRepr<U, T> {
+    coeff: Vec<Vec<T>>,
+    state: Vec<Vec<U>>,
+    head: usize,
+}
+
+/// serialize a 2-d array as nested sequences, i.e., the same as `Vec<Vec<_>>`
+struct Rows<'a, X>(&'a Array2<X>);
+
+impl<X> Serialize for Rows<'_, X>
+where
+    X: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(self.0.outer_iter().map(Row))
+    }
+}
+
+struct Row<'a, X>(ArrayView1<'a, X>);
+
+impl<X> Serialize for Row<'_, X>
+where
+    X: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(self.0.iter())
+    }
+}
 
 ///////////////////////////////////
 /// FIR filter
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(
+    try_from = "BatchFilterRepr<U, T>",
+    bound(deserialize = "U: Deserialize<'de>, T: Deserialize<'de>")
+)]
 pub struct BatchFilter<U, T>
 where
     T: std::fmt::Debug,
     U: std::fmt::Debug,
 {
-    /// reversed coefficients, i.e., impulse respone
-    //pub filters: Vec<filter::Filter<U, T>>,
-    pub coeff: Vec<Vec<T>>,
-    pub state: Vec<Vec<U>>,
+    /// reversed coefficients, i.e., impulse respone, with `tap` rows and `nch` columns
+    coeff: Array2<T>,
+    /// ring buffer of the last `tap` inputs, with `tap` rows and `nch` columns, row `head` being the oldest one
+    pub(crate) state: Array2<U>,
+    /// index of the oldest row of the state
     pub head: usize,
+    /// number of the latest consecutive time steps that are valid, see [`Self::feed_mask`], which is not serialized
+    pub(crate) valid_run: usize,
+}
+
+impl<U, T> Serialize for BatchFilter<U, T>
+where
+    T: std::fmt::Debug + Serialize,
+    U: std::fmt::Debug + Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut s = serializer.serialize_struct("BatchFilter", 3)?;
+        s.serialize_field("coeff", &Rows(&self.coeff))?;
+        s.serialize_field("state", &Rows(&self.state))?;
+        s.serialize_field("head", &self.head)?;
+        s.end()
+    }
+}
+
+impl<U, T> TryFrom<BatchFilterRepr<U, T>> for BatchFilter<U, T>
+where
+    T: std::fmt::Debug,
+    U: std::fmt::Debug,
+{
+    type Error = String;
+
+    fn try_from(repr: BatchFilterRepr<U, T>) -> Result<Self, Self::Error> {
+        let tap = repr.coeff.len();
+        let nch = repr.coeff.first().map_or(0, |x| x.len());
+        if repr.state.len() != tap {
+            return Err(format!(
+                "state has {} rows, while coeff has {tap}",
+                repr.state.len()
+            ));
+        }
+        if repr.head >= tap {
+            return Err(format!("head {} is not less than tap {tap}", repr.head));
+        }
+        if let Some(ncols) = repr
+            .coeff
+            .iter()
+            .map(|x| x.len())
+            .chain(repr.state.iter().map(|x| x.len()))
+            .find(|&n| n != nch)
+        {
+            return Err(format!(
+                "a row of coeff or state has {ncols} columns, while nch is {nch}"
+            ));
+        }
+        let coeff = Array2::from_shape_vec((tap, nch), repr.coeff.into_iter().flatten().collect())
+            .map_err(|e| e.to_string())?;
+        let state = Array2::from_shape_vec((tap, nch), repr.state.into_iter().flatten().collect())
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            coeff,
+            state,
+            head: repr.head,
+            valid_run: usize::MAX,
+        })
+    }
+}
+
+/// filter `signal`, with one row for each time step, by the `coeff` and the ring buffer `state` starting at `head`,
+/// and write the result to `out`, which has the same shape as `signal`
+pub(crate) fn filter_block_impl<U, T>(
+    coeff: ArrayView2<T>,
+    mut state: ArrayViewMut2<U>,
+    head: usize,
+    signal: ArrayView2<U>,
+    mut out: ArrayViewMut2<U>,
+) where
+    T: Copy,
+    U: Copy + Add<U, Output = U> + Mul<T, Output = U> + Default,
+{
+    let tap = coeff.nrows();
+    let mut head = head;
+    signal
+        .outer_iter()
+        .zip(out.outer_iter_mut())
+        .for_each(|(x, mut y)| {
+            state.row_mut((head + tap - 1) % tap).assign(&x);
+            y.fill(U::default());
+            coeff.outer_iter().enumerate().for_each(|(i, c)| {
+                y.iter_mut()
+                    .zip(state.row((head + i) % tap))
+                    .zip(c)
+                    .for_each(|((y1, &a), &b)| *y1 = *y1 + a * b);
+            });
+            head = (head + 1) % tap;
+        });
 }
 
 impl<U, T> BatchFilter<U, T>
@@ -39,103 +173,259 @@ where
         + std::fmt::Debug,
     Complex<T>: std::convert::From<U> + std::fmt::Debug,
 {
-    /// construct a FIR with its coefficients    
+    /// construct a FIR with its coefficients
+    /// * `coeff` - coefficients with `nch` rows and `tap` columns
     pub fn new(coeff: ArrayView2<T>) -> Self {
-        //coeff.reverse();
-        //let tap = coeff.shape()[0];
         let nch = coeff.shape()[0];
         let tap = coeff.shape()[1];
 
         let coeff = coeff
-            .axis_iter(Axis(1))
-            .rev()
-            .map(|x| {
-                let x = x.to_owned();
-                x.to_vec()
-            })
-            .collect::<Vec<_>>();
-        let state = (0..tap)
-            .map(|_| vec![<U as Default>::default(); nch])
-            .collect();
+            .t()
+            .slice(s![..;-1, ..])
+            .as_standard_layout()
+            .to_owned();
+        let state = Array2::<U>::default((tap, nch));
 
         Self {
             coeff,
             state,
             head: 0,
+            valid_run: usize::MAX,
         }
     }
 
+    /// number of channels
+    pub fn nch(&self) -> usize {
+        self.coeff.ncols()
+    }
+
+    /// number of taps of each channel
+    pub fn tap(&self) -> usize {
+        self.coeff.nrows()
+    }
+
+    /// reversed coefficients, i.e., impulse respone, with `tap` rows and `nch` columns
+    pub fn coeff(&self) -> ArrayView2<'_, T> {
+        self.coeff.view()
+    }
+
+    /// ring buffer of the last `tap` inputs, with `tap` rows and `nch` columns, row [`Self::head`] being the oldest one
+    pub fn state(&self) -> ArrayView2<'_, U> {
+        self.state.view()
+    }
+
+    /// filter one time step of all channels, and write the result to `out`, without any allocation
+    /// * `signal` - input of all channels, the length of which should be `nch`
+    /// * `out` - output of all channels, the length of which should be `nch`
+    pub fn filter_into(&mut self, signal: &[U], out: &mut [U]) {
+        let nch = self.nch();
+        assert_eq!(nch, signal.len());
+        assert_eq!(nch, out.len());
+        let signal = ArrayView2::from_shape((1, nch), signal).unwrap();
+        let out = ArrayViewMut2::from_shape((1, nch), out).unwrap();
+        filter_block_impl(
+            self.coeff.view(),
+            self.state.view_mut(),
+            self.head,
+            signal,
+            out,
+        );
+        self.head = (self.head + 1) % self.tap();
+    }
+
     /// filter a time series signal
     /// return the filtered signal
     pub fn filter(&mut self, signal: &[U]) -> Vec<U> {
-        let nch = self.coeff[0].len();
-        let tap = self.coeff.len();
-        assert_eq!(nch, signal.len());
+        let mut result = vec![U::default(); self.nch()];
+        self.filter_into(signal, &mut result);
+        result
+    }
 
-        self.state[(self.head + tap - 1) % tap].clone_from_slice(signal);
+    /// filter many time steps at once
+    /// * `signal` - input, with one row for each time step and `nch` columns
+    /// * `out` - output, with the same shape as `signal`
+    pub fn filter_block(&mut self, signal: ArrayView2<U>, out: ArrayViewMut2<U>) {
+        assert_eq!(signal.ncols(), self.nch());
+        assert_eq!(signal.shape(), out.shape());
+        filter_block_impl(
+            self.coeff.view(),
+            self.state.view_mut(),
+            self.head,
+            signal,
+            out,
+        );
+        self.head = (self.head + signal.nrows()) % self.tap();
+    }
 
-        let result = self
-            .state
-            .iter()
-            .cycle()
-            .skip(self.head)
-            .zip(self.coeff.iter())
-            .map(|(a, b)| {
-                a.iter()
-                    .zip(b.iter())
-                    .map(|(&a1, &b1)| a1 * b1)
-                    .collect::<Vec<_>>()
-            })
-            .reduce(|a, b| {
-                a.iter()
-                    .zip(b.iter())
-                    .map(|(&a1, &b1)| a1 + b1)
-                    .collect::<Vec<_>>()
-            })
-            .unwrap();
+    /// parallel version of [`Self::filter_block`], the channels are divided into groups, which are filtered in parallel
+    pub fn filter_block_par(&mut self, signal: ArrayView2<U>, mut out: ArrayViewMut2<U>) {
+        let nch = self.nch();
+        assert_eq!(signal.ncols(), nch);
+        assert_eq!(signal.shape(), out.shape());
+        let chunk = nch.div_ceil(rayon::current_num_threads()).max(1);
+        let head = self.head;
+        self.coeff
+            .axis_chunks_iter(Axis(1), chunk)
+            .into_par_iter()
+            .zip(
+                self.state
+                    .axis_chunks_iter_mut(Axis(1), chunk)
+                    .into_par_iter(),
+            )
+            .zip(signal.axis_chunks_iter(Axis(1), chunk).into_par_iter())
+            .zip(out.axis_chunks_iter_mut(Axis(1), chunk).into_par_iter())
+            .for_each(|(((c, s), x), y)| filter_block_impl(c, s, head, x, y));
+        self.head = (self.head + signal.nrows()) % self.tap();
+    }
 
-        self.head = (self.head + 1) % tap;
-        assert_eq!(result.len(), nch);
-        result
+    /// parallel version of [`Self::filter_into`]
+    pub fn filter_into_par(&mut self, signal: &[U], out: &mut [U]) {
+        let nch = self.nch();
+        assert_eq!(nch, signal.len());
+        assert_eq!(nch, out.len());
+        let signal = ArrayView2::from_shape((1, nch), signal).unwrap();
+        let out = ArrayViewMut2::from_shape((1, nch), out).unwrap();
+        self.filter_block_par(signal, out);
     }
 
     pub fn feed(&mut self, signal: &[U]) {
-        let nch = self.coeff[0].len();
-        let tap = self.coeff.len();
+        let nch = self.nch();
+        let tap = self.tap();
         assert_eq!(nch, signal.len());
-        self.state[(self.head + tap - 1) % tap].clone_from_slice(signal);
+        self.state
+            .row_mut((self.head + tap - 1) % tap)
+            .iter_mut()
+            .zip(signal)
+            .for_each(|(a, &b)| *a = b);
         self.head = (self.head + 1) % tap;
     }
 
+    /// record the validity of the next time step, which is filtered or fed separately.
+    /// The initial state is regarded as valid.
+    /// * `valid` - whether all the inputs of the time step are valid
+    /// * return value - whether the output of the time step is valid, i.e., all the `tap` time steps in the state are valid
+    pub fn feed_mask(&mut self, valid: bool) -> bool {
+        self.valid_run = if valid {
+            self.valid_run.saturating_add(1)
+        } else {
+            0
+        };
+        self.valid_run >= self.tap()
+    }
+
+    /// record the validity of the input samples, which are divided into time steps of `nch` samples
+    /// * `mask_buffer` - validity of the buffered samples that are not enough for a time step,
+    ///   which is resized to `nbuffered` first, regarding the unknown ones as valid, and is updated to the remaining samples
+    /// * `nbuffered` - number of buffered samples of the signal
+    /// * `mask` - validity of the new samples
+    /// * return value - validity of the output of each time step, see [`Self::feed_mask`]
+    pub(crate) fn feed_mask_buffered(
+        &mut self,
+        mask_buffer: &mut Vec<bool>,
+        nbuffered: usize,
+        mask: &[bool],
+    ) -> Vec<bool> {
+        let nch = self.nch();
+        mask_buffer.resize(nbuffered, true);
+        let batch = (mask_buffer.len() + mask.len()) / nch;
+        mask_buffer.extend_from_slice(mask);
+        let result = mask_buffer
+            .chunks_exact(nch)
+            .take(batch)
+            .map(|m| self.feed_mask(m.iter().all(|&v| v)))
+            .collect();
+        mask_buffer.drain(..batch * nch);
+        result
+    }
+
     pub fn filter_par(&mut self, signal: &[U]) -> Vec<U> {
-        let nch = self.coeff[0].len();
-        let tap = self.coeff.len();
-        assert_eq!(nch, signal.len());
+        let mut result = vec![U::default(); self.nch()];
+        self.filter_into_par(signal, &mut result);
+        result
+    }
+}
 
-        self.state[(self.head + tap - 1) % tap].clone_from_slice(signal);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cspfb::tests::white_noise, utils::polyphase_decomp, windowed_fir::pfb_coeff};
 
-        let result = (0..tap)
-            .into_par_iter()
-            .zip(self.coeff.par_iter())
-            .map(|(i, b)| {
-                self.state[(i + self.head) % tap]
-                    .iter()
-                    .zip(b.iter())
-                    .map(|(&a1, &b1)| a1 * b1)
-                    .collect::<Vec<_>>()
-            })
-            .reduce(
-                || vec![<U as Default>::default(); nch],
-                |a, b| {
-                    a.iter()
-                        .zip(b.iter())
-                        .map(|(&a1, &b1)| a1 + b1)
-                        .collect::<Vec<_>>()
-                },
-            );
+    #[test]
+    fn block_test() {
+        let nch = 8;
+        let tap = 4;
+        let coeff = pfb_coeff::<f64>(nch, tap, 1.1);
+        let coeff = polyphase_decomp(coeff.as_slice().unwrap(), nch);
+        let signal = white_noise(nch * 50, 3);
+        let signal = ArrayView2::from_shape((50, nch), &signal).unwrap();
 
-        self.head = (self.head + 1) % tap;
-        assert_eq!(result.len(), nch);
-        result
+        let mut filter1 = BatchFilter::<Complex<f64>, f64>::new(coeff.view());
+        let mut filter2 = filter1.clone();
+        let mut filter3 = filter1.clone();
+
+        let y1 = signal
+            .outer_iter()
+            .flat_map(|x| filter1.filter(x.as_slice().unwrap()))
+            .collect::<Vec<_>>();
+
+        let mut y2 = Array2::<Complex<f64>>::default((50, nch));
+        filter2.filter_block(signal.slice(s![..17, ..]), y2.slice_mut(s![..17, ..]));
+        filter2.filter_block_par(signal.slice(s![17.., ..]), y2.slice_mut(s![17.., ..]));
+
+        let mut y3 = Array2::<Complex<f64>>::default((50, nch));
+        signal
+            .outer_iter()
+            .zip(y3.outer_iter_mut())
+            .for_each(|(x, mut y)| {
+                filter3.filter_into_par(x.as_slice().unwrap(), y.as_slice_mut().unwrap())
+            });
+
+        // direct convolution of each channel
+        (0..nch).for_each(|ch| {
+            (0..50).for_each(|n| {
+                let expected = (0..tap.min(n + 1))
+                    .map(|k| signal[(n - k, ch)] * coeff[(ch, k)])
+                    .sum::<Complex<f64>>();
+                assert!((y1[n * nch + ch] - expected).norm() < 1e-12);
+            });
+        });
+        assert!(y1
+            .iter()
+            .zip(y2.iter())
+            .all(|(a, b)| (a - b).norm() < 1e-12));
+        assert!(y1
+            .iter()
+            .zip(y3.iter())
+            .all(|(a, b)| (a - b).norm() < 1e-12));
+        assert_eq!(filter1.head, filter2.head);
+    }
+
+    #[test]
+    fn serde_test() {
+        let coeff = ndarray::array![[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]];
+        let mut filter = BatchFilter::<f64, f64>::new(coeff.view());
+        filter.feed(&[1.0, 2.0]);
+        let s = serde_yaml::to_string(&filter).unwrap();
+        // the same as the form of the previous `Vec<Vec<_>>` based implementation
+        let expected = "coeff:\n- - 3.0\n  - 6.0\n- - 2.0\n  - 5.0\n- - 1.0\n  - 4.0\nstate:\n- - 0.0\n  - 0.0\n- - 0.0\n  - 0.0\n- - 1.0\n  - 2.0\nhead: 1\n";
+        assert_eq!(s, expected);
+        let filter2: BatchFilter<f64, f64> = serde_yaml::from_str(&s).unwrap();
+        assert_eq!(filter2.coeff, filter.coeff);
+        assert_eq!(filter2.state, filter.state);
+        assert_eq!(filter2.head, filter.head);
+    }
+
+    #[test]
+    fn malformed_serde_test() {
+        for s in [
+            // state shorter than coeff
+            "coeff:\n- - 3.0\n- - 2.0\nstate:\n- - 0.0\nhead: 0\n",
+            // head out of range
+            "coeff:\n- - 3.0\n- - 2.0\nstate:\n- - 0.0\n- - 1.0\nhead: 2\n",
+            // ragged rows
+            "coeff:\n- - 3.0\n  - 6.0\n- - 2.0\nstate:\n- - 0.0\n  - 0.0\n- - 1.0\n  - 2.0\nhead: 1\n",
+        ] {
+            assert!(serde_yaml::from_str::<BatchFilter<f64, f64>>(s).is_err());
+        }
     }
 }
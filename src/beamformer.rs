@@ -0,0 +1,228 @@
+//! Phased-array beamformer working on channelized data
+
+use crate::channelizer::ChannelFrequency;
+use ndarray::{
+    parallel::prelude::*, Array2, Array3, ArrayView2, ArrayView3, ArrayViewMut2, Axis, Zip,
+};
+use num::{
+    complex::Complex,
+    traits::{Float, FloatConst},
+};
+
+/// Beamformer, which forms several coherent beams at once from multi-antenna channelized data.
+///
+/// Beam `b` is `sum(w[b, ant, ch]*x[ant, ch, t])` over all antennas,
+/// where `w` are the complex weights of the shape `(nbeam, nant, nch)`
+#[derive(Debug, Clone)]
+pub struct Beamformer<T> {
+    /// complex weights of the shape `(nbeam, nant, nch)`
+    weights: Array3<Complex<T>>,
+}
+
+impl<T> Beamformer<T>
+where
+    T: Float + FloatConst + std::fmt::Debug + Send + Sync,
+{
+    /// constructor, all the weights are initialized to 1, i.e., all beams point to the phase center
+    /// * `nbeam` - number of beams
+    /// * `nant` - number of antennas
+    /// * `nch` - number of channels
+    /// ```
+    /// extern crate rsdsp;
+    /// use num::complex::Complex;
+    /// use ndarray::Array3;
+    /// use rsdsp::beamformer::Beamformer;
+    ///
+    /// let mut bf=Beamformer::<f64>::new(2, 4, 8);
+    /// // beam 1 is steered by flipping the sign of antenna 3
+    /// bf.weights_mut(1).row_mut(3).fill(Complex::new(-1.0, 0.0));
+    /// let data=Array3::from_elem((4, 8, 10), Complex::new(1.0, 0.0));
+    /// let beams=bf.form_beams(data.view());
+    /// assert_eq!(beams.shape(), &[2, 8, 10]);
+    /// assert_eq!(beams[[0, 0, 0]], Complex::new(4.0, 0.0));
+    /// assert_eq!(beams[[1, 0, 0]], Complex::new(2.0, 0.0));
+    /// ```
+    pub fn new(nbeam: usize, nant: usize, nch: usize) -> Self {
+        Self {
+            weights: Array3::from_elem((nbeam, nant, nch), Complex::new(T::one(), T::zero())),
+        }
+    }
+
+    /// number of beams
+    pub fn nbeam(&self) -> usize {
+        self.weights.len_of(Axis(0))
+    }
+
+    /// number of antennas
+    pub fn nant(&self) -> usize {
+        self.weights.len_of(Axis(1))
+    }
+
+    /// number of channels
+    pub fn nch(&self) -> usize {
+        self.weights.len_of(Axis(2))
+    }
+
+    /// weights of all beams, of the shape `(nbeam, nant, nch)`
+    pub fn weights(&self) -> ArrayView3<'_, Complex<T>> {
+        self.weights.view()
+    }
+
+    /// weights of beam `b`, of the shape `(nant, nch)`, which can be updated in place between frames
+    pub fn weights_mut(&mut self, b: usize) -> ArrayViewMut2<'_, Complex<T>> {
+        self.weights.index_axis_mut(Axis(0), b)
+    }
+
+    /// set the weights of beam `b`
+    /// * `weights` - weights of the shape `(nant, nch)`
+    pub fn set_weights(&mut self, b: usize, weights: ArrayView2<Complex<T>>) {
+        self.weights_mut(b).assign(&weights);
+    }
+
+    /// set the weights of beam `b` from geometric delays, i.e., the weight of antenna `ant` in channel `k`
+    /// is `exp(2*pi*i*(f_k+lo_freq)*delays[ant])`, which compensates the delays in the same way as
+    /// [`crate::delay_comp::DelayCompensator`]
+    /// * `delays` - geometric delay of each antenna, in seconds
+    /// * `freqs` - frequencies of the channels, the center frequencies of which are relative to the DC of the input signal
+    /// * `lo_freq` - frequency of the local oscillator
+    pub fn set_delays(
+        &mut self,
+        b: usize,
+        delays: &[T],
+        freqs: &[ChannelFrequency<T>],
+        lo_freq: T,
+    ) {
+        assert_eq!(delays.len(), self.nant());
+        assert_eq!(freqs.len(), self.nch());
+        let two = T::one() + T::one();
+        self.weights_mut(b)
+            .outer_iter_mut()
+            .zip(delays)
+            .for_each(|(mut w, &d)| {
+                w.iter_mut().zip(freqs).for_each(|(w, f)| {
+                    *w = Complex::from_polar(T::one(), two * T::PI() * (f.center + lo_freq) * d);
+                })
+            });
+    }
+
+    /// form the coherent beams, the channels are processed in parallel
+    /// * `data` - channelized data of the shape `(nant, nch, ntime)`
+    /// * return value - beams of the shape `(nbeam, nch, ntime)`
+    pub fn form_beams(&self, data: ArrayView3<Complex<T>>) -> Array3<Complex<T>> {
+        assert_eq!(data.len_of(Axis(0)), self.nant());
+        assert_eq!(data.len_of(Axis(1)), self.nch());
+        let ntime = data.len_of(Axis(2));
+        let mut result = Array3::from_elem(
+            (self.nbeam(), self.nch(), ntime),
+            Complex::new(T::zero(), T::zero()),
+        );
+        result
+            .axis_iter_mut(Axis(1))
+            .into_par_iter()
+            .zip(data.axis_iter(Axis(1)).into_par_iter())
+            .zip(self.weights.axis_iter(Axis(2)).into_par_iter())
+            .for_each(|((mut beams, x), w)| {
+                // beams: (nbeam, ntime), x: (nant, ntime), w: (nbeam, nant)
+                beams
+                    .outer_iter_mut()
+                    .zip(w.outer_iter())
+                    .for_each(|(mut beam, w)| {
+                        x.outer_iter().zip(w.iter()).for_each(|(x, &w)| {
+                            Zip::from(&mut beam)
+                                .and(&x)
+                                .for_each(|b, &x| *b = *b + w * x);
+                        })
+                    })
+            });
+        result
+    }
+
+    /// incoherent beam, i.e., the power summed over all antennas, the channels are processed in parallel
+    /// * `data` - channelized data of the shape `(nant, nch, ntime)`
+    /// * return value - power of the shape `(nch, ntime)`
+    pub fn incoherent_sum(&self, data: ArrayView3<Complex<T>>) -> Array2<T> {
+        assert_eq!(data.len_of(Axis(0)), self.nant());
+        assert_eq!(data.len_of(Axis(1)), self.nch());
+        let mut result = Array2::from_elem((self.nch(), data.len_of(Axis(2))), T::zero());
+        result
+            .outer_iter_mut()
+            .into_par_iter()
+            .zip(data.axis_iter(Axis(1)).into_par_iter())
+            .for_each(|(mut p, x)| {
+                x.outer_iter().for_each(|x| {
+                    Zip::from(&mut p)
+                        .and(&x)
+                        .for_each(|p, &x| *p = *p + x.norm_sqr());
+                })
+            });
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cspfb::tests::white_noise, utils::fftfreq};
+    use ndarray::Array1;
+
+    #[test]
+    fn delay_steering_test() {
+        let nant = 5;
+        let nch = 16;
+        let ntime = 20;
+        let sample_rate = 16e6;
+        let lo_freq = 1.4e9;
+        let freqs: Vec<_> = fftfreq::<f64>(nch)
+            .into_iter()
+            .map(|f| ChannelFrequency {
+                center: f * sample_rate,
+                bandwidth: sample_rate / nch as f64,
+                sample_rate: sample_rate / nch as f64,
+            })
+            .collect();
+        let delays: Vec<_> = (0..nant).map(|a| a as f64 * 1.7e-9).collect();
+        let s = Array2::from_shape_vec((nch, ntime), white_noise(nch * ntime, 3)).unwrap();
+        // the signal of the source arrives with the geometric delays
+        let data = Array3::from_shape_fn((nant, nch, ntime), |(a, k, t)| {
+            s[[k, t]]
+                * Complex::from_polar(
+                    1.0,
+                    -2.0 * f64::PI() * (freqs[k].center + lo_freq) * delays[a],
+                )
+        });
+        let mut bf = Beamformer::<f64>::new(2, nant, nch);
+        bf.set_delays(0, &delays, &freqs, lo_freq);
+        let beams = bf.form_beams(data.view());
+        beams
+            .index_axis(Axis(0), 0)
+            .iter()
+            .zip(s.iter())
+            .for_each(|(b, s)| assert!((b - s * nant as f64).norm() < 1e-9));
+        // the beam pointing to the phase center loses coherence
+        let p0: f64 = beams
+            .index_axis(Axis(0), 0)
+            .iter()
+            .map(|x| x.norm_sqr())
+            .sum();
+        let p1: f64 = beams
+            .index_axis(Axis(0), 1)
+            .iter()
+            .map(|x| x.norm_sqr())
+            .sum();
+        assert!(p1 < p0 * 0.5);
+
+        // the weights are updated in place
+        let ptr = bf.weights().as_ptr();
+        let w = Array2::from_shape_fn((nant, nch), |(a, k)| bf.weights()[[0, a, k]]);
+        bf.set_weights(1, w.view());
+        assert_eq!(ptr, bf.weights().as_ptr());
+        let beams = bf.form_beams(data.view());
+        assert_eq!(beams.index_axis(Axis(0), 0), beams.index_axis(Axis(0), 1));
+
+        let p = bf.incoherent_sum(data.view());
+        let expected: Array1<f64> = s.iter().map(|x| x.norm_sqr() * nant as f64).collect();
+        p.iter()
+            .zip(expected.iter())
+            .for_each(|(a, b)| assert!((a - b).abs() < 1e-9));
+    }
+}
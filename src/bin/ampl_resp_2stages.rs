@@ -5,10 +5,12 @@ use std::fs::File;
 use rsdsp::{
     ampl_resp::ampl_resp_2stages_1freq,
     cfg::{PfbCfg, TwoStageCfg},
+    csp_pfb::CspPfb,
+    cspfb, ospfb,
     windowed_fir::pfb_coeff,
 };
 
-use num::traits::FloatConst;
+use num::{complex::Complex, traits::FloatConst};
 
 use serde_yaml::from_reader;
 
@@ -27,27 +29,41 @@ type FloatType = f64;
 struct Args {
     /// config
     #[clap(short('c'), long("cfg"), value_name = "config file")]
-    ch_cfg_file:String, 
-
-    #[clap(short('f'), long("fmin"), value_name="minimum freq", default_value="-1")]
+    ch_cfg_file: String,
+
+    #[clap(
+        short('f'),
+        long("fmin"),
+        value_name = "minimum freq",
+        default_value = "-1"
+    )]
     fmin: FloatType,
 
-    #[clap(short('F'), long("fmax"), value_name="maximum freq", default_value="1")]
+    #[clap(
+        short('F'),
+        long("fmax"),
+        value_name = "maximum freq",
+        default_value = "1"
+    )]
     fmax: FloatType,
 
-    #[clap(short('n'), long("nfreq"), value_name="num of freq", default_value="1024")]
+    #[clap(
+        short('n'),
+        long("nfreq"),
+        value_name = "num of freq",
+        default_value = "1024"
+    )]
     nfreq: usize,
 
-    #[clap(short('t'), long("niter"), value_name="niter", default_value="2")]
+    #[clap(short('t'), long("niter"), value_name = "niter", default_value = "2")]
     niter: usize,
 
-    #[clap(short('o'), long("out"), value_name="out")]
+    #[clap(short('o'), long("out"), value_name = "out")]
     outfile: String,
 }
 
-
 pub fn main() {
-    let args=Args::parse();
+    let args = Args::parse();
 
     let mut cfg_file = File::open(args.ch_cfg_file).unwrap();
     let TwoStageCfg {
@@ -56,12 +72,14 @@ pub fn main() {
                 nch: nch_coarse,
                 k: k_coarse,
                 tap_per_ch: tap_coarse,
+                ..
             },
         fine_cfg:
             PfbCfg {
                 nch: nch_fine,
                 k: k_fine,
                 tap_per_ch: tap_fine,
+                ..
             },
         selected_coarse_ch,
     } = from_reader(&mut cfg_file).unwrap();
@@ -72,9 +90,19 @@ pub fn main() {
     let niter = args.niter;
 
     let coeff_coarse =
-        pfb_coeff::<FloatType>(nch_coarse / 2, tap_coarse, k_coarse as FloatType).into_raw_vec();
-    let coeff_fine =
-        pfb_coeff::<FloatType>(nch_fine * 2, tap_fine, k_fine as FloatType).into_raw_vec();
+        pfb_coeff::<FloatType>(nch_coarse / 2, tap_coarse, k_coarse as FloatType).to_vec();
+    let coeff_fine = pfb_coeff::<FloatType>(nch_fine * 2, tap_fine, k_fine as FloatType).to_vec();
+
+    // center frequencies of the fine channels, in the same unit as the input frequencies
+    let coarse_freqs =
+        ospfb::Analyzer::<Complex<FloatType>, FloatType>::new(nch_coarse, &coeff_coarse)
+            .channel_frequencies(2.0 * FloatType::PI(), 0.0);
+    let fine_freqs = CspPfb::new(
+        &selected_coarse_ch,
+        &cspfb::Analyzer::<Complex<FloatType>, FloatType>::new(nch_fine * 2, &coeff_fine),
+    )
+    .channel_frequencies(&coarse_freqs);
+    let fine_freqs = Array1::from(fine_freqs.iter().map(|f| f.center).collect::<Vec<_>>());
 
     let signal_len = coeff_coarse.len() + coeff_fine.len() * nch_coarse / 2;
     println!("signal length={}", signal_len);
@@ -115,6 +143,7 @@ pub fn main() {
     npz.add_array("freq", &freqs).unwrap();
     npz.add_array("coarse", &coarse_spec).unwrap();
     npz.add_array("fine", &fine_spec).unwrap();
+    npz.add_array("fine_freq", &fine_freqs).unwrap();
     npz.add_array(
         "coarse_ch",
         &ArrayView1::from(&selected_coarse_ch).map(|&x| x as i32),
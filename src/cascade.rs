@@ -0,0 +1,404 @@
+//! Cascaded channelizer with any number of stages, e.g., coarse, fine and zoom stages,
+//! constructed from [`crate::cfg::MultiStageCfg`]
+
+use crate::{
+    cfg::MultiStageCfg,
+    channelizer::{ChannelFrequency, Channelizer, OutputLayout},
+    csp_pfb::CspPfb,
+    cspfb, rational_ospfb,
+    timing::{GroupDelay, Timing},
+    windowed_fir::pfb_coeff,
+};
+use ndarray::{Array2, ScalarOperand};
+use num::{
+    complex::Complex,
+    traits::{Float, FloatConst, NumAssign},
+};
+
+use rustfft::FftNum;
+use serde::{Deserialize, Serialize};
+use std::{
+    iter::Sum,
+    ops::{Add, Mul},
+};
+
+/// A channelizer, the first stage of which channelizes the input signal,
+/// and each of the following stages further channelizes some channels of the previous stage
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CascadeChannelizer<T>
+where
+    T: std::fmt::Debug + Float + FftNum,
+{
+    /// the first stage, which works on the input signal
+    first: rational_ospfb::Analyzer<Complex<T>, T>,
+    /// the following stages, each one working on the output of the previous one
+    stages: Vec<CspPfb<T>>,
+}
+
+impl<T> CascadeChannelizer<T>
+where
+    T: Copy
+        + Float
+        + FloatConst
+        + std::ops::MulAssign<T>
+        + ScalarOperand
+        + NumAssign
+        + std::fmt::Debug
+        + Sum
+        + Sync
+        + Send
+        + FftNum,
+    Complex<T>: Copy
+        + Add<Complex<T>, Output = Complex<T>>
+        + Mul<T, Output = Complex<T>>
+        + Mul<Complex<T>, Output = Complex<T>>
+        + Sum
+        + Default
+        + ScalarOperand
+        + Sync,
+{
+    /// constructor
+    /// * `first` - the first stage
+    /// * `stages` - the following stages, the coarse channels of each one are the output channels of the previous one
+    pub fn new(first: rational_ospfb::Analyzer<Complex<T>, T>, stages: Vec<CspPfb<T>>) -> Self {
+        Self { first, stages }
+    }
+
+    /// construct from cfg, the first stage is a [`rational_ospfb::Analyzer`] and the others are [`CspPfb`]s.
+    /// Each of the following stages retains `nch*Q/P` fine channels in each channel of the previous stage,
+    /// where `(P, Q)` is the oversampling factor of the previous stage, which is required for the first stage
+    /// and should be `None` or `(1, 1)` for the others
+    /// * `cfg` - cfg
+    /// ```
+    /// extern crate rsdsp;
+    /// use num::complex::Complex;
+    /// use rsdsp::{
+    ///     cfg::{MultiStageCfg, PfbCfg, StageCfg}
+    ///     , cascade::CascadeChannelizer
+    ///     , channelizer::Channelizer
+    /// };
+    ///
+    /// let cfg=MultiStageCfg{
+    ///     stages: vec![
+    ///         StageCfg{pfb: PfbCfg{nch: 16, tap_per_ch: 8, k: 1.1, oversampling: Some((4, 3))}, selected_ch: vec![]},
+    ///         StageCfg{pfb: PfbCfg{nch: 16, tap_per_ch: 8, k: 1.1, oversampling: None}, selected_ch: vec![0, 1]},
+    ///         StageCfg{pfb: PfbCfg{nch: 8, tap_per_ch: 8, k: 1.1, oversampling: None}, selected_ch: vec![3]},
+    ///     ]
+    /// };
+    /// let mut cascade=CascadeChannelizer::<f64>::from_cfg(&cfg);
+    /// // 12 fine channels are retained in each coarse channel, and all the 8 zoom channels are retained
+    /// assert_eq!(cascade.nch_of_stages(), vec![16, 24, 8]);
+    /// let input_signal=vec![Complex::<f64>::default(); 12*16*8*2];
+    /// let output=cascade.analyze(&input_signal);
+    /// assert_eq!(output.shape(), &[8, 2]);
+    /// ```
+    pub fn from_cfg(cfg: &MultiStageCfg) -> Self {
+        let (first_cfg, rest) = cfg.stages.split_first().expect("at least one stage");
+        assert!(first_cfg.selected_ch.is_empty());
+        let first = rational_ospfb::cfg2pfb(&first_cfg.pfb);
+        let mut oversampling = first_cfg.pfb.oversampling.unwrap();
+        let stages = rest
+            .iter()
+            .map(|stage| {
+                let (p, q) = oversampling;
+                let nfine = stage.pfb.nch * q / p;
+                assert_eq!(nfine * p, stage.pfb.nch * q);
+                oversampling = stage.pfb.oversampling.unwrap_or((1, 1));
+                assert!(oversampling.0 > 0 && oversampling.0 == oversampling.1);
+                let coeff = pfb_coeff::<T>(
+                    stage.pfb.nch,
+                    stage.pfb.tap_per_ch,
+                    T::from(stage.pfb.k).unwrap(),
+                );
+                let pfb1 =
+                    cspfb::Analyzer::<Complex<T>, T>::new(stage.pfb.nch, coeff.as_slice().unwrap());
+                CspPfb::new(&stage.selected_ch, &pfb1).with_nfine_per_coarse(nfine)
+            })
+            .collect();
+        Self::new(first, stages)
+    }
+
+    /// number of stages
+    pub fn nstages(&self) -> usize {
+        self.stages.len() + 1
+    }
+
+    /// the `i`-th stage except the first one, e.g., to reselect its coarse channels at runtime,
+    /// see [`CspPfb::add_coarse_ch`], [`CspPfb::remove_coarse_ch`] and [`CspPfb::replace_coarse_ch`]
+    /// * `i` - index of the stage, which should be in `1..nstages()`
+    pub fn stage_mut(&mut self, i: usize) -> &mut CspPfb<T> {
+        assert!(i >= 1);
+        &mut self.stages[i - 1]
+    }
+
+    /// number of output channels of each stage
+    pub fn nch_of_stages(&self) -> Vec<usize> {
+        std::iter::once(self.first.nch())
+            .chain(self.stages.iter().map(|s| s.nch()))
+            .collect()
+    }
+
+    /// timing of the output frames of each stage, in the unit of the input samples of the first stage
+    pub fn timing_of_stages(&self) -> Vec<Timing> {
+        let mut result = vec![self.first.timing()];
+        self.stages.iter().for_each(|stage| {
+            let t = result.last().unwrap().then(&stage.timing());
+            result.push(t);
+        });
+        result
+    }
+
+    /// group delay of each stage, including those of all the previous stages,
+    /// in the unit of the input samples of the first stage and of the output frames of the stage
+    pub fn group_delay_of_stages(&self) -> Vec<GroupDelay> {
+        let timing = self.timing_of_stages();
+        let mut input = self.first.group_delay().input;
+        let mut result = vec![GroupDelay::new(input, timing[0].step)];
+        self.stages
+            .iter()
+            .zip(timing.windows(2))
+            .for_each(|(stage, t)| {
+                // the group delay of a stage is in the unit of the output frames of the previous stage
+                input += stage.group_delay().input * t[0].step;
+                result.push(GroupDelay::new(input, t[1].step));
+            });
+        result
+    }
+
+    /// the output channel of each stage, from which each output channel of the last stage is derived
+    /// * return value - one `Vec` for each output channel, containing its channel index in the output of each stage
+    pub fn channel_map(&self) -> Vec<Vec<usize>> {
+        let mut result: Vec<Vec<usize>> = (0..self.first.nch()).map(|c| vec![c]).collect();
+        self.stages.iter().for_each(|stage| {
+            let nfine = stage.nfine_per_coarse();
+            let mut next = vec![Vec::new(); stage.nch()];
+            stage
+                .coarse_ch_selected()
+                .iter()
+                .zip(stage.group_slots())
+                .for_each(|(&ch, g)| {
+                    (0..nfine).for_each(|r| {
+                        let mut path = result[ch].clone();
+                        path.push(g * nfine + r);
+                        next[g * nfine + r] = path;
+                    });
+                });
+            result = next;
+        });
+        result
+    }
+
+    /// frequencies of the output channels of each stage
+    /// * `sample_rate` - sampling rate of the input signal, `1` for normalized frequencies
+    /// * `center_freq` - physical frequency of the DC of the input signal, `0` for normalized frequencies
+    pub fn channel_frequencies_of_stages(
+        &self,
+        sample_rate: T,
+        center_freq: T,
+    ) -> Vec<Vec<ChannelFrequency<T>>> {
+        let mut result = vec![self.first.channel_frequencies(sample_rate, center_freq)];
+        self.stages.iter().for_each(|stage| {
+            let freqs = stage.channel_frequencies(result.last().unwrap());
+            result.push(freqs);
+        });
+        result
+    }
+
+    /// frequencies of the output channels of the last stage, one for each row of the output of [`Self::analyze`]
+    /// * `sample_rate` - sampling rate of the input signal, `1` for normalized frequencies
+    /// * `center_freq` - physical frequency of the DC of the input signal, `0` for normalized frequencies
+    pub fn channel_frequencies(&self, sample_rate: T, center_freq: T) -> Vec<ChannelFrequency<T>> {
+        self.channel_frequencies_of_stages(sample_rate, center_freq)
+            .pop()
+            .unwrap()
+    }
+
+    /// channelize the input signal, and return the output of every stage
+    /// * `input_signal` - input time series
+    /// * return value - output of each stage, with one row for each channel
+    pub fn analyze_stages(&mut self, input_signal: &[Complex<T>]) -> Vec<Array2<Complex<T>>> {
+        let mut result = vec![self.first.analyze(input_signal)];
+        self.stages.iter_mut().for_each(|stage| {
+            let y = stage.analyze(result.last().unwrap().view());
+            result.push(y);
+        });
+        result
+    }
+
+    /// Parallel version of [`Self::analyze_stages`]
+    pub fn analyze_stages_par(&mut self, input_signal: &[Complex<T>]) -> Vec<Array2<Complex<T>>> {
+        let mut result = vec![self.first.analyze_par(input_signal)];
+        self.stages.iter_mut().for_each(|stage| {
+            let y = stage.analyze_par(result.last().unwrap().view());
+            result.push(y);
+        });
+        result
+    }
+
+    /// channelize the input signal
+    /// * `input_signal` - input time series
+    /// * return value - output of the last stage
+    pub fn analyze(&mut self, input_signal: &[Complex<T>]) -> Array2<Complex<T>> {
+        self.analyze_stages(input_signal).pop().unwrap()
+    }
+
+    /// Parallel version of [`Self::analyze`]
+    pub fn analyze_par(&mut self, input_signal: &[Complex<T>]) -> Array2<Complex<T>> {
+        self.analyze_stages_par(input_signal).pop().unwrap()
+    }
+}
+
+impl<T> Channelizer for CascadeChannelizer<T>
+where
+    T: Copy
+        + Float
+        + FloatConst
+        + std::ops::MulAssign<T>
+        + ScalarOperand
+        + NumAssign
+        + std::fmt::Debug
+        + Sum
+        + Sync
+        + Send
+        + FftNum,
+    Complex<T>: Copy
+        + Add<Complex<T>, Output = Complex<T>>
+        + Mul<T, Output = Complex<T>>
+        + Mul<Complex<T>, Output = Complex<T>>
+        + Sum
+        + Default
+        + ScalarOperand
+        + Sync,
+{
+    type Input = [Complex<T>];
+    type Output = Complex<T>;
+
+    fn nch(&self) -> usize {
+        *self.nch_of_stages().last().unwrap()
+    }
+
+    fn output_layout(&self) -> OutputLayout {
+        self.stages
+            .last()
+            .map_or(self.first.output_layout(), |s| s.output_layout())
+    }
+
+    fn predict_output_length(&self, input_len: usize) -> usize {
+        self.stages
+            .iter()
+            .fold(self.first.predict_output_length(input_len), |len, stage| {
+                stage.predict_output_length(len)
+            })
+    }
+
+    fn timing(&self) -> Timing {
+        self.timing_of_stages().pop().unwrap()
+    }
+
+    fn group_delay(&self) -> GroupDelay {
+        self.group_delay_of_stages().pop().unwrap()
+    }
+
+    fn analyze(&mut self, input: &[Complex<T>]) -> Array2<Complex<T>> {
+        CascadeChannelizer::analyze(self, input)
+    }
+
+    fn analyze_par(&mut self, input: &[Complex<T>]) -> Array2<Complex<T>> {
+        CascadeChannelizer::analyze_par(self, input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ampl_resp::ampl_resp_cascade_1freq,
+        cfg::{PfbCfg, StageCfg},
+        cspfb::tests::white_noise,
+    };
+    use ndarray::Axis;
+
+    fn cfg() -> MultiStageCfg {
+        let pfb = |nch, oversampling| PfbCfg {
+            nch,
+            tap_per_ch: 8,
+            k: 1.1,
+            oversampling,
+        };
+        MultiStageCfg {
+            stages: vec![
+                StageCfg {
+                    pfb: pfb(16, Some((4, 3))),
+                    selected_ch: vec![],
+                },
+                StageCfg {
+                    pfb: pfb(16, None),
+                    selected_ch: vec![0, 1],
+                },
+                StageCfg {
+                    pfb: pfb(8, None),
+                    selected_ch: vec![14, 3],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn cascade_test() {
+        let mut cascade = CascadeChannelizer::<f64>::from_cfg(&cfg());
+        let mut cascade_par = CascadeChannelizer::<f64>::from_cfg(&cfg());
+        assert_eq!(cascade.nstages(), 3);
+        assert_eq!(cascade.nch_of_stages(), vec![16, 24, 16]);
+        let map = cascade.channel_map();
+        assert_eq!(map[0], vec![1, 14, 0]);
+        assert_eq!(map[9], vec![0, 3, 9]);
+
+        let signal = white_noise(12 * 16 * 8 * 4, 9);
+        let y = signal
+            .chunks(1000)
+            .map(|x| cascade.analyze(x))
+            .reduce(|a, b| ndarray::concatenate![Axis(1), a, b])
+            .unwrap();
+        let y_par = cascade_par.analyze_par(&signal);
+        assert_eq!(y.shape(), &[16, 4]);
+        assert_eq!(y.shape(), y_par.shape());
+        assert!(y
+            .iter()
+            .zip(y_par.iter())
+            .all(|(a, b)| (a - b).norm() < 1e-12));
+    }
+
+    #[test]
+    fn frequency_test() {
+        let freqs = CascadeChannelizer::<f64>::from_cfg(&cfg()).channel_frequencies(1.0, 0.0);
+        [0, 3, 6, 9, 13].iter().for_each(|&ch| {
+            let f = freqs[ch].center + 0.2 * freqs[ch].bandwidth;
+            let mut cascade = CascadeChannelizer::<f64>::from_cfg(&cfg());
+            let resp =
+                ampl_resp_cascade_1freq(&mut cascade, f * 2.0 * f64::PI(), 12 * 16 * 8 * 16, 2);
+            let peak = resp[2]
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+                .unwrap()
+                .0;
+            assert_eq!(peak, ch);
+        });
+    }
+
+    #[test]
+    fn group_delay_test() {
+        let cascade = CascadeChannelizer::<f64>::from_cfg(&cfg());
+        let gd = cascade.group_delay_of_stages();
+        // (16*8-1)/2 input samples, then (16*8-1)/2 and (8*8-1)/2 frames of the previous stage
+        assert_eq!(gd[0].input, 63.5);
+        assert_eq!(gd[1].input, 63.5 + 63.5 * 12.0);
+        assert_eq!(gd[2].input, 63.5 + 63.5 * 12.0 + 31.5 * 12.0 * 16.0);
+        crate::channelizer::tests::check_group_delay(
+            || CascadeChannelizer::<f64>::from_cfg(&cfg()),
+            12 * 16 * 8 * 30,
+            12345,
+            1,
+        );
+    }
+}
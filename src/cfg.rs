@@ -13,6 +13,12 @@ pub struct PfbCfg {
 
     // pass band width factor 1.1 should be a common value
     pub k: f64,
+
+    /// oversampling factor (P, Q), i.e., the output rate of each channel is P/Q times of the channel spacing.
+    /// It is required by [`crate::rational_ospfb::cfg2pfb`], and ignored by `cspfb` and the 2x `ospfb`,
+    /// the oversampling factors of which are fixed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oversampling: Option<(usize, usize)>,
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
@@ -30,3 +36,19 @@ pub struct DelayerCfg {
     /// 2*half_tap+1 is the filter tap
     pub half_tap: usize,
 }
+
+/// cfg of one stage of [`MultiStageCfg`]
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct StageCfg {
+    /// filter bank of this stage, only the first stage can be oversampled
+    pub pfb: PfbCfg,
+    /// channels of the previous stage that are further channelized by this stage, which should be empty for the first stage
+    #[serde(default)]
+    pub selected_ch: Vec<usize>,
+}
+
+/// cfg of a cascaded channelizer, e.g., coarse, fine and zoom stages
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct MultiStageCfg {
+    pub stages: Vec<StageCfg>,
+}
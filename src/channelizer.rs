@@ -0,0 +1,506 @@
+//! A common interface of all filter banks, so that tools can be generic over them
+
+use crate::{
+    timing::{GroupDelay, Timing},
+    utils::fftfreq,
+};
+use ndarray::{Array2, ArrayViewMut2, Axis};
+use num::traits::Float;
+use serde::{Deserialize, Serialize};
+
+/// Order of the rows of the output of [`Channelizer::analyze`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputLayout {
+    /// row `i` is centered at `fftfreq(nch)[i]` of the input sampling rate,
+    /// i.e., the DC channel first, then positive channels, then negative ones
+    FftOrder,
+    /// row `i` is centered at `i/nch_full` of the input sampling rate, for `i` in `0..=nch_full/2`,
+    /// i.e., only the non-negative channels of a real input, see [`crate::utils::add_neg_freq`]
+    NonNegative {
+        /// number of channels including both pos and neg ones
+        nch_full: usize,
+    },
+    /// one group of rows for each selected coarse channel, in increasing frequency within each group
+    CoarseGrouped {
+        /// number of rows in each group
+        nfine_per_coarse: usize,
+    },
+}
+
+/// Frequency of one output channel, i.e., one row of the output of [`Channelizer::analyze`]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ChannelFrequency<T> {
+    /// center frequency of the channel
+    pub center: T,
+    /// bandwidth of the channel, i.e., the spacing between adjacent channels
+    pub bandwidth: T,
+    /// sampling rate of the channelized data, which is larger than `bandwidth` if the channel is oversampled
+    pub sample_rate: T,
+}
+
+impl<T> ChannelFrequency<T>
+where
+    T: Float,
+{
+    /// convert a frequency normalized by the input sampling rate, i.e., in the unit of cycles per input sample,
+    /// to a physical one
+    /// * `sample_rate` - sampling rate of the input signal
+    /// * `center_freq` - physical frequency of the DC of the input signal, e.g., the LO frequency
+    pub fn to_physical(self, sample_rate: T, center_freq: T) -> Self {
+        Self {
+            center: self.center * sample_rate + center_freq,
+            bandwidth: self.bandwidth * sample_rate,
+            sample_rate: self.sample_rate * sample_rate,
+        }
+    }
+
+    /// normalized frequencies of `nch` channels in fft order, each of which is sampled at `oversampling` times of the channel spacing
+    pub(crate) fn fft_order(nch: usize, oversampling: T) -> Vec<Self> {
+        let bandwidth = T::one() / T::from(nch).unwrap();
+        fftfreq(nch)
+            .into_iter()
+            .map(|center| Self {
+                center,
+                bandwidth,
+                sample_rate: bandwidth * oversampling,
+            })
+            .collect()
+    }
+}
+
+/// Memory layout of the output buffer passed to `analyze_into`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataLayout {
+    /// one row for each channel and one column for each frame, i.e., the same as the output of `analyze`
+    ChannelMajor,
+    /// one row for each frame and one column for each channel, i.e., the same as the output of `analyze_raw`
+    TimeMajor,
+}
+
+impl DataLayout {
+    /// the axis along which the frames are arranged
+    pub fn frame_axis(self) -> Axis {
+        match self {
+            DataLayout::ChannelMajor => Axis(1),
+            DataLayout::TimeMajor => Axis(0),
+        }
+    }
+
+    /// the axis along which the channels are arranged
+    pub fn channel_axis(self) -> Axis {
+        match self {
+            DataLayout::ChannelMajor => Axis(0),
+            DataLayout::TimeMajor => Axis(1),
+        }
+    }
+
+    /// shape of a buffer holding `nframes` frames of `nch` channels
+    pub fn shape(self, nch: usize, nframes: usize) -> [usize; 2] {
+        match self {
+            DataLayout::ChannelMajor => [nch, nframes],
+            DataLayout::TimeMajor => [nframes, nch],
+        }
+    }
+}
+
+/// Filter banks, which channelize a stream of input and output a 2-d array with one row for each channel
+pub trait Channelizer {
+    /// input of [`Self::analyze`], e.g., `[R]` for time domain signal,
+    /// or the 2-d output of a coarser [`Channelizer`]
+    type Input: ?Sized;
+
+    /// element type of the channelized data
+    type Output;
+
+    /// number of output channels, i.e., rows of the output of [`Self::analyze`]
+    fn nch(&self) -> usize;
+
+    /// order of the output channels
+    fn output_layout(&self) -> OutputLayout;
+
+    /// number of output frames, i.e., columns, that an input with `input_len` samples (or frames) will produce
+    fn predict_output_length(&self, input_len: usize) -> usize;
+
+    /// timing of the output frames in the unit of the input samples (or frames), including the intrinsic delay of the filters,
+    /// e.g., `timing().next_position()` is the position of the first output frame of the next call
+    fn timing(&self) -> Timing;
+
+    /// group delay of the filter bank, in the unit of the input samples (or frames) and of the output frames,
+    /// i.e., the number of input samples to be fed after an input sample until the output frame corresponding to it is produced
+    fn group_delay(&self) -> GroupDelay;
+
+    /// channelize the input, the remaining input that is not enough for an output frame is buffered for the next call
+    fn analyze(&mut self, input: &Self::Input) -> Array2<Self::Output>;
+
+    /// the same as [`Self::analyze`], and the timing of the output, the `next_output` of which is the index of its first frame
+    fn analyze_timed(&mut self, input: &Self::Input) -> (Array2<Self::Output>, Timing) {
+        let timing = self.timing();
+        (self.analyze(input), timing)
+    }
+
+    /// parallel version of [`Self::analyze`]
+    fn analyze_par(&mut self, input: &Self::Input) -> Array2<Self::Output>;
+
+    /// the same as [`Self::analyze`], but writing to `out`, the shape of which should be
+    /// `layout.shape(self.nch(), self.predict_output_length(input_len))`.
+    /// The default implementation copies the output of [`Self::analyze`]
+    fn analyze_into(
+        &mut self,
+        input: &Self::Input,
+        mut out: ArrayViewMut2<Self::Output>,
+        layout: DataLayout,
+    ) where
+        Self::Output: Clone,
+    {
+        let y = self.analyze(input);
+        match layout {
+            DataLayout::ChannelMajor => out.assign(&y),
+            DataLayout::TimeMajor => out.assign(&y.t()),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        csp_pfb::CspPfb, cspfb, cspfb::tests::white_noise, ospfb, ospfb2, rational_ospfb,
+        timing::tests::peak, windowed_fir::pfb_coeff,
+    };
+    use num::complex::Complex;
+
+    fn check_shape<C>(pfb: &mut C, input: &C::Input, input_len: usize)
+    where
+        C: Channelizer,
+    {
+        let n = pfb.predict_output_length(input_len);
+        let y = pfb.analyze(input);
+        assert_eq!(y.shape(), &[pfb.nch(), n]);
+        let n = pfb.predict_output_length(input_len);
+        let y = pfb.analyze_par(input);
+        assert_eq!(y.shape(), &[pfb.nch(), n]);
+    }
+
+    #[test]
+    fn shape_test() {
+        let nch = 16;
+        let coeff = pfb_coeff::<f64>(nch, 8, 1.1);
+        let coeff = coeff.as_slice().unwrap();
+        let coeff_half = pfb_coeff::<f64>(nch / 2, 8, 1.1);
+        let coeff_half = coeff_half.as_slice().unwrap();
+        let signal = white_noise(nch * 40, 6);
+
+        let mut pfb1 = cspfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff);
+        check_shape(&mut pfb1, &signal[..], signal.len());
+        let mut pfb2 = ospfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff_half);
+        check_shape(&mut pfb2, &signal[..], signal.len());
+        let mut pfb3 = ospfb2::Analyzer::<Complex<f64>, f64>::new(nch, coeff);
+        check_shape(&mut pfb3, &signal[..], signal.len());
+        let mut pfb4 = rational_ospfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff, (4, 3));
+        check_shape(&mut pfb4, &signal[..], signal.len());
+        let signal_real = signal.iter().map(|x| x.re).collect::<Vec<_>>();
+        let mut pfb5 = cspfb::RealAnalyzer::<f64>::new(nch, coeff);
+        check_shape(&mut pfb5, &signal_real[..], signal_real.len());
+
+        let coarse = pfb2.analyze(&signal);
+        let mut csp = CspPfb::new(&[1, 2, 3], &pfb1);
+        assert_eq!(
+            csp.output_layout(),
+            OutputLayout::CoarseGrouped {
+                nfine_per_coarse: nch / 2
+            }
+        );
+        check_shape(&mut csp, &coarse, coarse.ncols());
+    }
+
+    fn check_into<C>(pfb1: &mut C, pfb2: &mut C, input: &C::Input, input_len: usize)
+    where
+        C: Channelizer<Output = Complex<f64>>,
+    {
+        let n = pfb1.predict_output_length(input_len);
+        let y1 = pfb1.analyze(input);
+        let mut y2 = Array2::<Complex<f64>>::default(DataLayout::TimeMajor.shape(pfb2.nch(), n));
+        pfb2.analyze_into(input, y2.view_mut(), DataLayout::TimeMajor);
+        assert!(y1
+            .iter()
+            .zip(y2.t().iter())
+            .all(|(a, b)| (a - b).norm() < 1e-12));
+        let n = pfb1.predict_output_length(input_len);
+        // the parallel version still goes through the time-major path
+        let y1 = pfb1.analyze_par(input);
+        let mut y2 = Array2::<Complex<f64>>::default(DataLayout::ChannelMajor.shape(pfb2.nch(), n));
+        pfb2.analyze_into(input, y2.view_mut(), DataLayout::ChannelMajor);
+        assert!(y1
+            .iter()
+            .zip(y2.iter())
+            .all(|(a, b)| (a - b).norm() < 1e-12));
+    }
+
+    /// the valid output frames should not depend on the invalid input samples
+    fn check_masked<F>(mut analyze: F, x: &[Complex<f64>], mask: &[bool]) -> Vec<bool>
+    where
+        F: FnMut(usize, &[Complex<f64>], &[bool]) -> (Array2<Complex<f64>>, Vec<bool>),
+    {
+        let garbage = white_noise(x.len(), 100);
+        let x_garbage: Vec<_> = x
+            .iter()
+            .zip(&garbage)
+            .zip(mask)
+            .map(|((&a, &b), &m)| if m { a } else { b * 1e3 })
+            .collect();
+        let split = x.len() / 3 + 5;
+        let mut y1 = Vec::new();
+        let mut y2 = Vec::new();
+        let mut frame_mask = Vec::new();
+        [(0, split), (split, x.len())].iter().for_each(|&(a, b)| {
+            let (y, m) = analyze(0, &x[a..b], &mask[a..b]);
+            let (y_garbage, m_garbage) = analyze(1, &x_garbage[a..b], &mask[a..b]);
+            assert_eq!(m, m_garbage);
+            assert_eq!(y.ncols(), m.len());
+            y1.extend(y.columns().into_iter().map(|c| c.to_owned()));
+            y2.extend(y_garbage.columns().into_iter().map(|c| c.to_owned()));
+            frame_mask.extend(m);
+        });
+        y1.iter()
+            .zip(&y2)
+            .zip(&frame_mask)
+            .for_each(|((a, b), &valid)| {
+                let diff = a.iter().zip(b).map(|(a, b)| (a - b).norm()).sum::<f64>();
+                assert_eq!(diff < 1e-9, valid);
+            });
+        frame_mask
+    }
+
+    #[test]
+    fn masked_test() {
+        let nch = 16;
+        let tap = 4;
+        let coeff = pfb_coeff::<f64>(nch, tap, 1.1);
+        let coeff = coeff.as_slice().unwrap();
+        let coeff_half = pfb_coeff::<f64>(nch / 2, tap, 1.1);
+        let coeff_half = coeff_half.as_slice().unwrap();
+        let signal = white_noise(nch * 60, 8);
+        let mut mask = vec![true; signal.len()];
+        // a lost packet
+        mask[nch * 20 + 3..nch * 22 + 1].fill(false);
+
+        let mut pfb = [
+            cspfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff),
+            cspfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff),
+        ];
+        let frame_mask = check_masked(|i, x, m| pfb[i].analyze_masked(x, m), &signal, &mask);
+        // frames from 20 to 22+tap-1 contain the invalid samples in their windows
+        frame_mask.iter().enumerate().for_each(|(i, &valid)| {
+            assert_eq!(valid, !(20..22 + tap).contains(&i));
+        });
+
+        let mut pfb = [
+            ospfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff_half),
+            ospfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff_half),
+        ];
+        let frame_mask = check_masked(|i, x, m| pfb[i].analyze_masked(x, m), &signal, &mask);
+        assert_eq!(frame_mask.iter().filter(|&&v| !v).count(), 4 + tap);
+
+        let mut pfb = [
+            ospfb2::Analyzer::<Complex<f64>, f64>::new(nch, coeff),
+            ospfb2::Analyzer::<Complex<f64>, f64>::new(nch, coeff),
+        ];
+        check_masked(|i, x, m| pfb[i].analyze_masked(x, m), &signal, &mask);
+
+        // the coarse frame mask is propagated to the fine channels
+        let mut coarse = ospfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff_half);
+        let (x, coarse_mask) = coarse.analyze_masked(&signal, &mask);
+        let coarse_mask = Array2::from_shape_fn(x.dim(), |(_, t)| coarse_mask[t]);
+        let coeff_fine = pfb_coeff::<f64>(4, 2, 1.1);
+        let template = cspfb::Analyzer::<Complex<f64>, f64>::new(4, coeff_fine.as_slice().unwrap());
+        let mut csp = CspPfb::new(&[1, 5], &template);
+        let (y, fine_mask) = csp.analyze_masked(x.view(), coarse_mask.view());
+        assert_eq!(y.dim(), fine_mask.dim());
+        // 4+tap invalid coarse frames, i.e., 2 or 3 fine frames contain them, each one contaminating 2 frames
+        let ninvalid = fine_mask.row(0).iter().filter(|&&v| !v).count();
+        assert!((3..=5).contains(&ninvalid), "{ninvalid}");
+        assert!(fine_mask.outer_iter().all(|r| r == fine_mask.row(0)));
+    }
+
+    #[test]
+    fn analyze_into_test() {
+        let nch = 16;
+        let coeff = pfb_coeff::<f64>(nch, 8, 1.1);
+        let coeff = coeff.as_slice().unwrap();
+        let coeff_half = pfb_coeff::<f64>(nch / 2, 8, 1.1);
+        let coeff_half = coeff_half.as_slice().unwrap();
+        let signal = white_noise(nch * 40, 7);
+
+        let mut pfb1 = cspfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff);
+        let mut pfb2 = cspfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff);
+        check_into(&mut pfb1, &mut pfb2, &signal[..], signal.len());
+        let mut pfb1 = ospfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff_half);
+        let mut pfb2 = ospfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff_half);
+        check_into(&mut pfb1, &mut pfb2, &signal[..], signal.len());
+        let mut pfb1 = ospfb2::Analyzer::<Complex<f64>, f64>::new(nch, coeff);
+        let mut pfb2 = ospfb2::Analyzer::<Complex<f64>, f64>::new(nch, coeff);
+        check_into(&mut pfb1, &mut pfb2, &signal[..], signal.len());
+
+        let coarse = ospfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff_half).analyze(&signal);
+        let template = cspfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff);
+        let mut csp1 = CspPfb::new(&[1, 2, 3], &template);
+        let mut csp2 = CspPfb::new(&[1, 2, 3], &template);
+        check_into(&mut csp1, &mut csp2, &coarse, coarse.ncols());
+    }
+
+    /// the output frame with the max power should be the nearest one to the position of an impulse
+    fn check_timing<C>(pfb: &mut C, n: usize, n0: usize, chunk: usize)
+    where
+        C: Channelizer<Input = [Complex<f64>], Output = Complex<f64>>,
+    {
+        let mut x = vec![Complex::<f64>::default(); n];
+        x[n0] = Complex::new(1.0, 0.0);
+        let mut power = Vec::new();
+        x.chunks(chunk).for_each(|x1| {
+            let (y, timing) = pfb.analyze_timed(x1);
+            assert_eq!(timing.next_output, power.len());
+            power.extend(
+                y.columns()
+                    .into_iter()
+                    .map(|c| c.iter().map(|x| x.norm_sqr()).sum::<f64>()),
+            );
+        });
+        let timing = pfb.timing();
+        assert_eq!(timing.next_output, power.len());
+        let peak = peak(&power);
+        let pos = timing.position(peak);
+        assert!((pos - n0 as f64).abs() <= timing.step / 2.0, "{pos} {n0}");
+    }
+
+    #[test]
+    fn timing_test() {
+        let nch = 16;
+        let coeff = pfb_coeff::<f64>(nch, 8, 1.1);
+        let coeff = coeff.as_slice().unwrap();
+        let coeff_half = pfb_coeff::<f64>(nch / 2, 8, 1.1);
+        let coeff_half = coeff_half.as_slice().unwrap();
+        let n = nch * 50;
+        [300, 333, 345].into_iter().for_each(|n0| {
+            check_timing(
+                &mut cspfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff),
+                n,
+                n0,
+                37,
+            );
+            check_timing(
+                &mut ospfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff_half),
+                n,
+                n0,
+                37,
+            );
+            // the even and the odd frames are produced in pairs only if the input is of multiples of `nch`
+            check_timing(
+                &mut ospfb2::Analyzer::<Complex<f64>, f64>::new(nch, coeff),
+                n,
+                n0,
+                nch * 3,
+            );
+            check_timing(
+                &mut rational_ospfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff, (4, 3)),
+                n,
+                n0,
+                37,
+            );
+        });
+
+        // the timing of the fine frames in the unit of the input samples of the coarse channelizer
+        let n0 = 3000;
+        let mut x = vec![Complex::<f64>::default(); nch * 400];
+        x[n0] = Complex::new(1.0, 0.0);
+        let mut coarse = ospfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff_half);
+        let y = coarse.analyze(&x);
+        let coeff_fine = pfb_coeff::<f64>(4, 4, 1.1);
+        let template = cspfb::Analyzer::<Complex<f64>, f64>::new(4, coeff_fine.as_slice().unwrap());
+        let mut csp = CspPfb::new(&[0, 1, 15], &template);
+        let z = csp.analyze(y.view());
+        let timing = coarse.timing().then(&csp.timing());
+        assert_eq!(timing.step, (nch / 2 * 4) as f64);
+        assert_eq!(timing.next_output, z.ncols());
+        let power: Vec<f64> = z
+            .columns()
+            .into_iter()
+            .map(|c| c.iter().map(|x| x.norm_sqr()).sum())
+            .collect();
+        let peak = peak(&power);
+        assert!((timing.position(peak) - n0 as f64).abs() <= timing.step / 2.0);
+    }
+
+    /// an impulse at `n0` should appear in the frame that is produced when `group_delay` more input samples are fed,
+    /// i.e., the frame with the max power is the first one produced by `n0+1+group_delay.input` input samples
+    /// * `stride` - number of frames produced together, e.g., 2 for the even and odd frames of the ospfb2,
+    ///   the frames of each position in a group are checked separately with a tolerance of `stride/2` hops,
+    ///   taking into account that a frame waits one hop for each later frame in its group
+    pub(crate) fn check_group_delay<C, F>(new_pfb: F, n: usize, n0: usize, stride: usize)
+    where
+        C: Channelizer<Input = [Complex<f64>], Output = Complex<f64>>,
+        F: Fn() -> C,
+    {
+        let mut x = vec![Complex::<f64>::default(); n];
+        x[n0] = Complex::new(1.0, 0.0);
+        let y = new_pfb().analyze(&x);
+        let power: Vec<f64> = y
+            .columns()
+            .into_iter()
+            .map(|c| c.iter().map(|x| x.norm_sqr()).sum())
+            .collect();
+        let pfb = new_pfb();
+        let gd = pfb.group_delay();
+        let step = pfb.timing().step;
+        assert!((gd.output * step - gd.input).abs() < 1e-9);
+        (0..stride).for_each(|i| {
+            let power1: Vec<f64> = power.iter().skip(i).step_by(stride).copied().collect();
+            let peak = i + peak(&power1) * stride;
+            let nneeded = (1..=n)
+                .find(|&len| pfb.predict_output_length(len) > peak)
+                .unwrap();
+            let lag = (stride - 1 - i) as f64 * step;
+            let diff = nneeded as f64 - 1.0 - n0 as f64 - gd.input - lag;
+            assert!(
+                diff.abs() <= stride as f64 / 2.0 * step,
+                "{i} {diff} {step}"
+            );
+        });
+    }
+
+    #[test]
+    fn group_delay_test() {
+        let nch = 16;
+        let coeff = pfb_coeff::<f64>(nch, 8, 1.1);
+        let coeff = coeff.as_slice().unwrap();
+        let coeff_half = pfb_coeff::<f64>(nch / 2, 8, 1.1);
+        let coeff_half = coeff_half.as_slice().unwrap();
+        let n = nch * 50;
+        [300, 309, 333, 345].into_iter().for_each(|n0| {
+            check_group_delay(
+                || cspfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff),
+                n,
+                n0,
+                1,
+            );
+            check_group_delay(
+                || ospfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff_half),
+                n,
+                n0,
+                1,
+            );
+            // the even and the odd frames are output in pairs
+            check_group_delay(
+                || ospfb2::Analyzer::<Complex<f64>, f64>::new(nch, coeff),
+                n,
+                n0,
+                2,
+            );
+            check_group_delay(
+                || rational_ospfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff, (4, 3)),
+                n,
+                n0,
+                1,
+            );
+        });
+    }
+}
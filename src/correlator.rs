@@ -0,0 +1,403 @@
+//! Multi-antenna FX correlator working on channelized data
+
+use ndarray::{parallel::prelude::*, s, Array3, ArrayView3, Axis};
+use num::{complex::Complex, traits::Float};
+
+/// Index of the baseline `(i, j)` with `i<=j` among `nant` antennas.
+/// The baselines are ordered as `(0,0), (0,1), ..., (0,nant-1), (1,1), (1,2), ..., (nant-1,nant-1)`,
+/// i.e., the autos are included, and `i` varies the slowest
+pub fn baseline_index(nant: usize, i: usize, j: usize) -> usize {
+    assert!(i <= j && j < nant);
+    i * nant - i * (i + 1) / 2 + j
+}
+
+/// All baselines among `nant` antennas, in the order of [`baseline_index`]
+pub fn baselines(nant: usize) -> Vec<(usize, usize)> {
+    (0..nant)
+        .flat_map(|i| (i..nant).map(move |j| (i, j)))
+        .collect()
+}
+
+/// Polarization products computed by a [`Correlator`] for each baseline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolProducts {
+    /// only the parallel products `(p, p)`, e.g., `XX, YY` for `npol=2`
+    Parallel,
+    /// all the `npol*npol` products `(p, q)`, ordered as `p*npol+q`, e.g., `XX, XY, YX, YY` for `npol=2`
+    Full,
+}
+
+impl PolProducts {
+    /// the polarization pairs `(p, q)` of the products, in the order of the products of each baseline
+    pub fn pairs(self, npol: usize) -> Vec<(usize, usize)> {
+        match self {
+            PolProducts::Parallel => (0..npol).map(|p| (p, p)).collect(),
+            PolProducts::Full => (0..npol)
+                .flat_map(|p| (0..npol).map(move |q| (p, q)))
+                .collect(),
+        }
+    }
+}
+
+/// FX correlator, which computes the visibilities of all baselines, including the autos,
+/// integrated over a configurable number of frames.
+///
+/// The input data are of the shape `(nant*npol, nch, ntime)`, with the input `ant*npol+pol` for polarization `pol` of antenna `ant`.
+/// The visibility of baseline `(i, j)` and polarization product `(p, q)` is `sum(x[i*npol+p]*conj(x[j*npol+q]))`,
+/// the polarization products of each baseline are selected by [`PolProducts`], all of them by default.
+#[derive(Debug, Clone)]
+pub struct Correlator<T> {
+    /// number of antennas
+    nant: usize,
+    /// number of polarizations of each antenna
+    npol: usize,
+    /// polarization products of each baseline
+    products: PolProducts,
+    /// number of frames integrated in each output
+    int_len: usize,
+    /// visibilities being integrated, of the shape `(nbl, nprod, nch)`
+    acc: Array3<Complex<T>>,
+    /// number of valid frames accumulated in each element of `acc`
+    count: Array3<usize>,
+    /// number of frames that have been integrated in `acc`
+    nacc: usize,
+}
+
+impl<T> Correlator<T>
+where
+    T: Float + std::fmt::Debug + Send + Sync,
+{
+    /// constructor
+    /// * `nant` - number of antennas
+    /// * `npol` - number of polarizations of each antenna, i.e., 1 for single-polarization input and 2 for dual polarizations
+    /// * `nch` - number of channels
+    /// * `int_len` - number of frames integrated in each output
+    /// ```
+    /// extern crate rsdsp;
+    /// use num::complex::Complex;
+    /// use ndarray::Array3;
+    /// use rsdsp::correlator::{Correlator, baseline_index};
+    ///
+    /// let mut correlator=Correlator::<f64>::new(3, 1, 4, 10);
+    /// let data=Array3::from_elem((3, 4, 25), Complex::new(1.0, 0.0));
+    /// let vis=correlator.correlate(data.view());
+    /// // two complete integrations, the remaining 5 frames are integrated with the next input
+    /// assert_eq!(vis.len(), 2);
+    /// assert_eq!(vis[0].shape(), &[6, 1, 4]);
+    /// assert_eq!(vis[0][[baseline_index(3, 1, 2), 0, 0]], Complex::new(10.0, 0.0));
+    /// ```
+    pub fn new(nant: usize, npol: usize, nch: usize, int_len: usize) -> Self {
+        assert!(nant > 0 && npol > 0 && int_len > 0);
+        let nbl = nant * (nant + 1) / 2;
+        Self {
+            nant,
+            npol,
+            products: PolProducts::Full,
+            int_len,
+            acc: Array3::from_elem((nbl, npol * npol, nch), Complex::new(T::zero(), T::zero())),
+            count: Array3::zeros((nbl, npol * npol, nch)),
+            nacc: 0,
+        }
+    }
+
+    /// select the polarization products, which should be called before any data are correlated
+    /// * `products` - polarization products of each baseline
+    /// ```
+    /// extern crate rsdsp;
+    /// use num::complex::Complex;
+    /// use ndarray::Array3;
+    /// use rsdsp::correlator::{Correlator, PolProducts};
+    ///
+    /// let mut correlator=Correlator::<f64>::new(2, 2, 4, 10).with_products(PolProducts::Parallel);
+    /// assert_eq!(correlator.pol_pairs(), &[(0, 0), (1, 1)]);
+    /// let data=Array3::from_elem((4, 4, 10), Complex::new(1.0, 0.0));
+    /// let vis=correlator.correlate(data.view());
+    /// // XX and YY of each of the 3 baselines
+    /// assert_eq!(vis[0].shape(), &[3, 2, 4]);
+    /// ```
+    pub fn with_products(mut self, products: PolProducts) -> Self {
+        assert_eq!(self.nacc, 0);
+        let (nbl, nch) = (self.nbl(), self.nch());
+        let nprod = products.pairs(self.npol).len();
+        self.products = products;
+        self.acc = Array3::from_elem((nbl, nprod, nch), Complex::new(T::zero(), T::zero()));
+        self.count = Array3::zeros((nbl, nprod, nch));
+        self
+    }
+
+    /// number of antennas
+    pub fn nant(&self) -> usize {
+        self.nant
+    }
+
+    /// number of polarizations of each antenna
+    pub fn npol(&self) -> usize {
+        self.npol
+    }
+
+    /// polarization products of each baseline
+    pub fn products(&self) -> PolProducts {
+        self.products
+    }
+
+    /// the polarization pairs `(p, q)` of the products of each baseline, see [`PolProducts::pairs`]
+    pub fn pol_pairs(&self) -> Vec<(usize, usize)> {
+        self.products.pairs(self.npol)
+    }
+
+    /// number of polarization products of each baseline
+    pub fn nprod(&self) -> usize {
+        self.acc.len_of(Axis(1))
+    }
+
+    /// number of channels
+    pub fn nch(&self) -> usize {
+        self.acc.len_of(Axis(2))
+    }
+
+    /// number of baselines, including the autos
+    pub fn nbl(&self) -> usize {
+        self.acc.len_of(Axis(0))
+    }
+
+    /// number of frames integrated in each output
+    pub fn int_len(&self) -> usize {
+        self.int_len
+    }
+
+    /// number of outputs that an input with `ntime` frames will produce
+    pub fn predict_output_length(&self, ntime: usize) -> usize {
+        (self.nacc + ntime) / self.int_len
+    }
+
+    /// accumulate the visibilities of the frames into `self.acc`, the channels are processed in parallel
+    /// * `flags` - flags of the samples to be excluded, of the same shape as `data`
+    fn accumulate(&mut self, data: ArrayView3<Complex<T>>, flags: Option<ArrayView3<bool>>) {
+        let npol = self.npol;
+        let pairs = self.pol_pairs();
+        let bl = baselines(self.nant);
+        self.acc
+            .axis_iter_mut(Axis(2))
+            .into_par_iter()
+            .zip(self.count.axis_iter_mut(Axis(2)).into_par_iter())
+            .zip(data.axis_iter(Axis(1)).into_par_iter())
+            .enumerate()
+            .for_each(|(ch, ((mut acc, mut count), x))| {
+                let valid = |input: usize, t: usize| flags.is_none_or(|f| !f[[input, ch, t]]);
+                bl.iter().enumerate().for_each(|(b, &(i, j))| {
+                    pairs.iter().enumerate().for_each(|(k, &(p, q))| {
+                        let (i1, i2) = (i * npol + p, j * npol + q);
+                        x.row(i1)
+                            .iter()
+                            .zip(x.row(i2).iter())
+                            .enumerate()
+                            .filter(|&(t, _)| valid(i1, t) && valid(i2, t))
+                            .for_each(|(_, (&x1, &x2))| {
+                                acc[[b, k]] = acc[[b, k]] + x1 * x2.conj();
+                                count[[b, k]] += 1;
+                            });
+                    })
+                })
+            });
+    }
+
+    fn correlate_impl(
+        &mut self,
+        data: ArrayView3<Complex<T>>,
+        flags: Option<ArrayView3<bool>>,
+    ) -> Vec<(Array3<Complex<T>>, Array3<usize>)> {
+        assert_eq!(data.len_of(Axis(0)), self.nant * self.npol);
+        assert_eq!(data.len_of(Axis(1)), self.nch());
+        if let Some(f) = flags {
+            assert_eq!(f.dim(), data.dim());
+        }
+        let ntime = data.len_of(Axis(2));
+        let mut result = Vec::with_capacity(self.predict_output_length(ntime));
+        let mut start = 0;
+        while start < ntime {
+            let n = (self.int_len - self.nacc).min(ntime - start);
+            self.accumulate(
+                data.slice(s![.., .., start..start + n]),
+                flags.map(|f| f.slice_move(s![.., .., start..start + n])),
+            );
+            self.nacc += n;
+            start += n;
+            if self.nacc == self.int_len {
+                result.push((self.acc.clone(), self.count.clone()));
+                self.acc.fill(Complex::new(T::zero(), T::zero()));
+                self.count.fill(0);
+                self.nacc = 0;
+            }
+        }
+        result
+    }
+
+    /// correlate the channelized data, the frames that are not enough for an integration are kept for the next call
+    /// * `data` - channelized data, of the shape `(nant*npol, nch, ntime)`
+    /// * return value - one array of the shape `(nbl, nprod, nch)` for each complete integration,
+    ///   see [`baseline_index`] for the order of the baselines and [`PolProducts`] for that of the products
+    pub fn correlate(&mut self, data: ArrayView3<Complex<T>>) -> Vec<Array3<Complex<T>>> {
+        self.correlate_impl(data, None)
+            .into_iter()
+            .map(|(vis, _)| vis)
+            .collect()
+    }
+
+    /// the same as [`Self::correlate`], but a product is excluded if either of the two samples is flagged,
+    /// see [`crate::rfi`]
+    /// * `flags` - flags of the same shape as `data`, `true` for the samples to be excluded
+    /// * return value - the visibilities and the number of valid frames accumulated in each of them, both of the shape `(nbl, nprod, nch)`
+    pub fn correlate_masked(
+        &mut self,
+        data: ArrayView3<Complex<T>>,
+        flags: ArrayView3<bool>,
+    ) -> Vec<(Array3<Complex<T>>, Array3<usize>)> {
+        self.correlate_impl(data, Some(flags))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cspfb::tests::white_noise,
+        utils::{corr, fft},
+    };
+
+    /// channelize real signals by fft of non-overlapping chunks, the same as [`corr`] does
+    fn fft_channelize(signals: &[Vec<f64>], nch: usize) -> Array3<Complex<f64>> {
+        let ntime = signals[0].len() / nch;
+        let mut result = Array3::zeros((signals.len(), nch, ntime));
+        signals.iter().enumerate().for_each(|(a, x)| {
+            x.chunks_exact(nch).enumerate().for_each(|(t, x1)| {
+                let x1: Vec<_> = x1.iter().map(|&x| Complex::new(x, 0.0)).collect();
+                result
+                    .slice_mut(s![a, .., t])
+                    .assign(&ndarray::Array1::from(fft(&x1[..])));
+            })
+        });
+        result
+    }
+
+    #[test]
+    fn two_antenna_test() {
+        let nch = 32;
+        let ntime = 50;
+        let x: Vec<_> = white_noise(nch * ntime, 1).iter().map(|x| x.re).collect();
+        let y: Vec<_> = x
+            .iter()
+            .zip(white_noise(nch * ntime, 2))
+            .map(|(&x, n)| x + 0.5 * n.re)
+            .collect();
+        let data = fft_channelize(&[x.clone(), y.clone()], nch);
+        let mut correlator = Correlator::<f64>::new(2, 1, nch, ntime);
+        // feed in two pieces, which should be integrated together
+        let mut vis = correlator.correlate(data.slice(s![.., .., ..17]));
+        assert!(vis.is_empty());
+        vis.extend(correlator.correlate(data.slice(s![.., .., 17..])));
+        assert_eq!(vis.len(), 1);
+        let vis = &vis[0];
+        [
+            ((0, 0), corr(&x, &x, nch)),
+            ((0, 1), corr(&x, &y, nch)),
+            ((1, 1), corr(&y, &y, nch)),
+        ]
+        .iter()
+        .for_each(|&((i, j), ref expected)| {
+            let b = baseline_index(2, i, j);
+            vis.slice(s![b, 0, ..])
+                .iter()
+                .zip(expected)
+                .for_each(|(a, b)| assert!((a - b).norm() < 1e-9 * b.norm().max(1.0)));
+        });
+    }
+
+    #[test]
+    fn masked_test() {
+        let nant = 3;
+        let nch = 4;
+        let ntime = 30;
+        let data =
+            Array3::from_shape_vec((nant, nch, ntime), white_noise(nant * nch * ntime, 4)).unwrap();
+        let flags = Array3::from_shape_fn((nant, nch, ntime), |(a, ch, t)| (a + ch * t) % 5 == 0);
+        let mut correlator = Correlator::<f64>::new(nant, 1, nch, ntime);
+        let result = correlator.correlate_masked(data.view(), flags.view());
+        assert_eq!(result.len(), 1);
+        let (vis, nvalid) = &result[0];
+        baselines(nant).iter().for_each(|&(i, j)| {
+            let b = baseline_index(nant, i, j);
+            (0..nch).for_each(|ch| {
+                let valid: Vec<_> = (0..ntime)
+                    .filter(|&t| !flags[[i, ch, t]] && !flags[[j, ch, t]])
+                    .collect();
+                let expected: Complex<f64> = valid
+                    .iter()
+                    .map(|&t| data[[i, ch, t]] * data[[j, ch, t]].conj())
+                    .sum();
+                assert_eq!(nvalid[[b, 0, ch]], valid.len());
+                assert!((vis[[b, 0, ch]] - expected).norm() < 1e-12);
+            })
+        });
+    }
+
+    #[test]
+    fn polarization_test() {
+        let nant = 3;
+        let npol = 2;
+        let nch = 8;
+        let ntime = 40;
+        let signals: Vec<Vec<f64>> = (0..nant * npol)
+            .map(|i| {
+                white_noise(nch * ntime, i as u64 + 10)
+                    .iter()
+                    .map(|x| x.re)
+                    .collect()
+            })
+            .collect();
+        let data = fft_channelize(&signals, nch);
+        let mut correlator = Correlator::<f64>::new(nant, npol, nch, 10);
+        let vis = correlator.correlate(data.view());
+        assert_eq!(vis.len(), 4);
+        assert_eq!(baselines(nant).len(), correlator.nbl());
+        baselines(nant).iter().for_each(|&(i, j)| {
+            let b = baseline_index(nant, i, j);
+            (0..npol).for_each(|p| {
+                (0..npol).for_each(|q| {
+                    let s1 = &signals[i * npol + p][..nch * 10];
+                    let s2 = &signals[j * npol + q][..nch * 10];
+                    vis[0]
+                        .slice(s![b, p * npol + q, ..])
+                        .iter()
+                        .zip(corr(s1, s2, nch))
+                        .for_each(|(a, b)| assert!((a - b).norm() < 1e-9 * b.norm().max(1.0)));
+                })
+            })
+        });
+    }
+
+    #[test]
+    fn parallel_products_test() {
+        let (nant, npol, nch, ntime) = (3, 2, 4, 20);
+        let data = Array3::from_shape_vec(
+            (nant * npol, nch, ntime),
+            white_noise(nant * npol * nch * ntime, 20),
+        )
+        .unwrap();
+        let mut full = Correlator::<f64>::new(nant, npol, nch, ntime);
+        let mut parallel =
+            Correlator::<f64>::new(nant, npol, nch, ntime).with_products(PolProducts::Parallel);
+        assert_eq!(parallel.nprod(), 2);
+        let vis_full = full.correlate(data.view());
+        let vis = parallel.correlate(data.view());
+        assert_eq!(vis[0].shape(), &[parallel.nbl(), 2, nch]);
+        // XX and YY are the 0th and the 3rd of the full products
+        assert_eq!(
+            vis[0].slice(s![.., 0, ..]),
+            vis_full[0].slice(s![.., 0, ..])
+        );
+        assert_eq!(
+            vis[0].slice(s![.., 1, ..]),
+            vis_full[0].slice(s![.., 3, ..])
+        );
+    }
+}
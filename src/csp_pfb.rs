@@ -1,8 +1,15 @@
 //! Central Signal Processor PFB
 //! Implemented with critical sampling pfb
 
-use crate::{cspfb, oscillator::HalfChShifter, utils::fftshift2};
-use ndarray::{parallel::prelude::*, s, Array2, ArrayView2, Axis, ScalarOperand};
+use crate::{
+    channelizer::{ChannelFrequency, Channelizer, DataLayout, OutputLayout},
+    cspfb,
+    oscillator::HalfChShifter,
+    timing::{GroupDelay, Timing},
+};
+use ndarray::{
+    parallel::prelude::*, s, Array2, ArrayView2, ArrayViewMut2, Axis, ScalarOperand, Slice,
+};
 use num::{
     complex::Complex,
     traits::{Float, FloatConst, NumAssign},
@@ -19,14 +26,26 @@ use std::{
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CspPfb<T>
 where
-    T: std::fmt::Debug + Float,
+    T: std::fmt::Debug + Float + FftNum,
 {
     /// an array of cspfbs, each one for one coarse channel that is selected
     pfb: Vec<cspfb::Analyzer<Complex<T>, T>>,
     /// coarse channels selected
-    pub coarse_ch_selected: Vec<usize>,
+    coarse_ch_selected: Vec<usize>,
     /// shift frequency in each coarse channel by half of the width of a fine channel
     shifter: HalfChShifter<T>,
+    /// number of fine channels retained in each coarse channel, `None` for half of the fine channels
+    #[serde(default)]
+    nfine_per_coarse: Option<usize>,
+    /// total number of coarse channels if the output is stitched, see [`Self::with_stitching`]
+    #[serde(default)]
+    stitch: Option<usize>,
+    /// factors of the shifter for each input frame
+    #[serde(skip, default = "Vec::new")]
+    phase: Vec<Complex<T>>,
+    /// shifted input of one coarse channel
+    #[serde(skip, default = "Vec::new")]
+    coarse_input: Vec<Complex<T>>,
 }
 
 impl<T> CspPfb<T>
@@ -63,45 +82,332 @@ where
             pfb,
             coarse_ch_selected,
             shifter,
+            nfine_per_coarse: None,
+            stitch: None,
+            phase: Vec::new(),
+            coarse_input: Vec::new(),
         }
     }
 
+    /// set the number of fine channels retained in the center of each coarse channel, which should be even.
+    /// For a coarse channelizer oversampled by `P/Q`, retaining `nch_fine*Q/P` fine channels makes the
+    /// fine channels of adjacent coarse channels neither overlap nor have gaps.
+    /// The default value is `nch_fine/2`, i.e., for 2x oversampled coarse channels
+    /// * `nfine_per_coarse` - number of retained fine channels
+    pub fn with_nfine_per_coarse(mut self, nfine_per_coarse: usize) -> Self {
+        assert!(nfine_per_coarse.is_multiple_of(2));
+        assert!(nfine_per_coarse <= self.pfb[0].nch());
+        self.nfine_per_coarse = Some(nfine_per_coarse);
+        self
+    }
+
+    /// output the groups of fine channels in the order of increasing frequency of their coarse channels,
+    /// rather than in the order of `coarse_ch_selected`, so that if the selected coarse channels are adjacent,
+    /// the output is a continuous spectrum with monotonically increasing frequency
+    /// * `nch_coarse` - total number of coarse channels, which are in fft order
+    /// ```
+    /// extern crate rsdsp;
+    /// use num::complex::Complex;
+    /// use rsdsp::{
+    ///     windowed_fir
+    ///     , cspfb
+    ///     , csp_pfb::CspPfb
+    /// };
+    ///
+    /// let nch_fine=32;
+    /// let coeff=windowed_fir::pfb_coeff::<f64>(nch_fine, 8, 1.1);
+    /// let fine_pfb=cspfb::Analyzer::<Complex<f64>, f64>::new(nch_fine, coeff.as_slice().unwrap());
+    /// // coarse channels 14, 15, 0, 1 of 16 are adjacent
+    /// let csp=CspPfb::new(&[0, 1, 14, 15], &fine_pfb).with_stitching(16);
+    /// assert_eq!(csp.group_slots(), vec![2, 3, 0, 1]);
+    /// ```
+    pub fn with_stitching(mut self, nch_coarse: usize) -> Self {
+        assert!(self.coarse_ch_selected.iter().all(|&c| c < nch_coarse));
+        self.stitch = Some(nch_coarse);
+        self
+    }
+
+    /// coarse channels selected, see [`Self::add_coarse_ch`], [`Self::remove_coarse_ch`] and [`Self::replace_coarse_ch`]
+    pub fn coarse_ch_selected(&self) -> &[usize] {
+        &self.coarse_ch_selected
+    }
+
+    /// an analyzer for a newly selected coarse channel, with zero state, aligned with the existing ones
+    /// * return value - the analyzer and the number of its transient output frames
+    fn new_pfb(&self, ch: usize) -> (cspfb::Analyzer<Complex<T>, T>, usize) {
+        if let Some(nch_coarse) = self.stitch {
+            assert!(ch < nch_coarse);
+        }
+        let mut pfb = self.pfb[0].clone();
+        let ntransient = pfb.clear_state();
+        (pfb, ntransient)
+    }
+
+    /// select one more coarse channel, the fine channels of which are appended to the output,
+    /// or placed according to its frequency if stitched.
+    /// The filter states of the other coarse channels are kept.
+    /// * `ch` - the coarse channel to be added
+    /// * return value - number of the following output frames of the new coarse channel that are transient,
+    ///   i.e., depend on the zero initial state of its filters
+    /// ```
+    /// extern crate rsdsp;
+    /// use num::complex::Complex;
+    /// use ndarray::Array2;
+    /// use rsdsp::{
+    ///     windowed_fir
+    ///     , cspfb
+    ///     , csp_pfb::CspPfb
+    /// };
+    ///
+    /// let nch_fine=16;
+    /// let coeff=windowed_fir::pfb_coeff::<f64>(nch_fine, 8, 1.1);
+    /// let fine_pfb=cspfb::Analyzer::<Complex<f64>, f64>::new(nch_fine, coeff.as_slice().unwrap());
+    /// let mut csp=CspPfb::new(&[0, 1], &fine_pfb);
+    /// let x=Array2::<Complex<f64>>::zeros((4, 100));
+    /// let _=csp.analyze(x.view());
+    /// let ntransient=csp.add_coarse_ch(3);
+    /// assert_eq!(ntransient, 8);
+    /// assert_eq!(csp.coarse_ch_selected(), &[0, 1, 3]);
+    /// let y=csp.analyze(x.view());
+    /// assert_eq!(y.shape(), &[3*8, 6]);
+    /// ```
+    pub fn add_coarse_ch(&mut self, ch: usize) -> usize {
+        assert!(
+            !self.coarse_ch_selected.contains(&ch),
+            "coarse channel {ch} has already been selected"
+        );
+        let (pfb, ntransient) = self.new_pfb(ch);
+        self.pfb.push(pfb);
+        self.coarse_ch_selected.push(ch);
+        ntransient
+    }
+
+    /// deselect a coarse channel, and remove its fine channels from the output.
+    /// The filter states of the other coarse channels are kept.
+    /// * `ch` - the coarse channel to be removed, which should not be the only selected one
+    pub fn remove_coarse_ch(&mut self, ch: usize) {
+        let g = self
+            .coarse_ch_selected
+            .iter()
+            .position(|&c| c == ch)
+            .expect("the coarse channel is not selected");
+        assert!(
+            self.coarse_ch_selected.len() > 1,
+            "at least one coarse channel should be selected"
+        );
+        self.pfb.remove(g);
+        self.coarse_ch_selected.remove(g);
+    }
+
+    /// replace a selected coarse channel with another one, the fine channels of which take the place of the old ones in the output,
+    /// unless stitched.
+    /// The filter states of the other coarse channels are kept.
+    /// * `old_ch` - the coarse channel to be removed
+    /// * `new_ch` - the coarse channel to be added
+    /// * return value - number of the following output frames of the new coarse channel that are transient
+    pub fn replace_coarse_ch(&mut self, old_ch: usize, new_ch: usize) -> usize {
+        let g = self
+            .coarse_ch_selected
+            .iter()
+            .position(|&c| c == old_ch)
+            .expect("the coarse channel is not selected");
+        assert!(
+            new_ch == old_ch || !self.coarse_ch_selected.contains(&new_ch),
+            "coarse channel {new_ch} has already been selected"
+        );
+        let (pfb, ntransient) = self.new_pfb(new_ch);
+        self.pfb[g] = pfb;
+        self.coarse_ch_selected[g] = new_ch;
+        ntransient
+    }
+
+    /// timing of the output frames, in the unit of the input coarse frames, see [`cspfb::Analyzer::timing`].
+    /// The timing in the unit of the samples of the original signal is
+    /// `coarse_pfb.timing().then(&self.timing())`, where `coarse_pfb` is the coarse channelizer.
+    /// The fine channelizers of all the selected coarse channels are aligned, even if added later
+    pub fn timing(&self) -> Timing {
+        self.pfb[0].timing()
+    }
+
+    /// group delay, in the unit of the input coarse frames and of the output fine frames,
+    /// the group delay of the coarse channelizer is not included
+    pub fn group_delay(&self) -> GroupDelay {
+        self.pfb[0].group_delay()
+    }
+
     pub fn nfine_per_coarse(&self) -> usize {
-        self.pfb[0].nch() / 2
+        self.nfine_per_coarse.unwrap_or(self.pfb[0].nch() / 2)
+    }
+
+    /// the position of the group of fine channels of each selected coarse channel in the output
+    pub fn group_slots(&self) -> Vec<usize> {
+        let mut slots: Vec<_> = (0..self.coarse_ch_selected.len()).collect();
+        if let Some(nch_coarse) = self.stitch {
+            let freq = |c: usize| {
+                if c < nch_coarse.div_ceil(2) {
+                    c as isize
+                } else {
+                    c as isize - nch_coarse as isize
+                }
+            };
+            let mut order = slots.clone();
+            order.sort_by_key(|&g| freq(self.coarse_ch_selected[g]));
+            order
+                .iter()
+                .enumerate()
+                .for_each(|(slot, &g)| slots[g] = slot);
+        }
+        slots
+    }
+
+    /// frequencies of the output fine channels, one for each row of the output of [`Self::analyze`]
+    /// * `coarse` - frequencies of all the coarse channels, indexed by the coarse channel number,
+    ///   e.g., [`crate::ospfb::Analyzer::normalized_channel_frequencies`] or [`crate::ospfb::Analyzer::channel_frequencies`]
+    /// * return value - frequencies in the same unit as `coarse`
+    /// ```
+    /// extern crate rsdsp;
+    /// use num::complex::Complex;
+    /// use rsdsp::{
+    ///     windowed_fir
+    ///     , cspfb
+    ///     , ospfb
+    ///     , csp_pfb::CspPfb
+    /// };
+    ///
+    /// let nch_coarse=16;
+    /// let nch_fine=8;
+    /// let coeff_coarse=windowed_fir::pfb_coeff::<f64>(nch_coarse/2, 12, 1.1);
+    /// let coeff_fine=windowed_fir::pfb_coeff::<f64>(nch_fine, 8, 1.1);
+    /// let coarse_pfb=ospfb::Analyzer::<Complex<f64>, f64>::new(nch_coarse, coeff_coarse.as_slice().unwrap());
+    /// let fine_pfb=cspfb::Analyzer::<Complex<f64>, f64>::new(nch_fine, coeff_fine.as_slice().unwrap());
+    /// let csp=CspPfb::new(&[15, 0], &fine_pfb).with_stitching(nch_coarse);
+    /// let freqs=csp.channel_frequencies(&coarse_pfb.channel_frequencies(1600.0, 0.0));
+    /// let centers:Vec<_>=freqs.iter().map(|f| f.center).collect();
+    /// assert_eq!(centers, vec![-137.5, -112.5, -87.5, -62.5, -37.5, -12.5, 12.5, 37.5]);
+    /// ```
+    pub fn channel_frequencies(&self, coarse: &[ChannelFrequency<T>]) -> Vec<ChannelFrequency<T>> {
+        let nch_fine = self.pfb[0].nch();
+        let nfine = self.nfine_per_coarse();
+        let mut result = vec![
+            ChannelFrequency {
+                center: T::zero(),
+                bandwidth: T::zero(),
+                sample_rate: T::zero()
+            };
+            self.coarse_ch_selected.len() * nfine
+        ];
+        self.coarse_ch_selected
+            .iter()
+            .zip(self.group_slots())
+            .for_each(|(&ch, g)| {
+                let fine_bw = coarse[ch].sample_rate / T::from(nch_fine).unwrap();
+                // the retained fine channels are centered at half channel below/above the center of the coarse channel
+                result[g * nfine..(g + 1) * nfine]
+                    .iter_mut()
+                    .enumerate()
+                    .for_each(|(r, f)| {
+                        let offset = T::from(2 * r + 1).unwrap() / T::from(2).unwrap()
+                            - T::from(nfine / 2).unwrap();
+                        *f = ChannelFrequency {
+                            center: coarse[ch].center + offset * fine_bw,
+                            bandwidth: fine_bw,
+                            sample_rate: fine_bw,
+                        };
+                    });
+            });
+        result
+    }
+
+    /// index of the `r`-th retained fine channel in the output of a fine channelizer
+    fn fine_index(&self, r: usize) -> usize {
+        let nch_fine = self.pfb[0].nch();
+        (r + (nch_fine - self.nfine_per_coarse()) / 2 + nch_fine / 2) % nch_fine
     }
 
-    #[allow(clippy::unused_enumerate_index)]
     /// Further channelize the input coarse channels to finer channels
     /// * `x` - input coarse channels, 2D array view, with `number of coarse channels` rows.
     /// * return value - a 2D array with fine channel data, with `number of fine channels` rows.
     pub fn analyze(&mut self, x: ArrayView2<Complex<T>>) -> Array2<Complex<T>> {
-        let mut x = x.select(Axis(0), &self.coarse_ch_selected);
-        x.axis_iter_mut(Axis(1))
-            .enumerate()
-            .for_each(|(_i, mut c)| {
-                let f = self.shifter.get();
-                c.iter_mut().for_each(|c1| {
-                    *c1 *= f;
-                })
+        let nch_output = self.coarse_ch_selected.len() * self.nfine_per_coarse();
+        let output_length = self.pfb[0].predict_output_length(x.ncols());
+        let mut result = Array2::<Complex<T>>::default((nch_output, output_length));
+        self.analyze_into(x, result.view_mut(), DataLayout::ChannelMajor);
+        result
+    }
+
+    /// Further channelize the input coarse channels with a validity mask, e.g., the frame mask of the coarse channelizer,
+    /// see [`cspfb::Analyzer::analyze_masked`]
+    /// * `x` - input coarse channels, 2D array view, with `number of coarse channels` rows.
+    /// * `mask` - validity of each input sample, with the same shape as `x`
+    /// * return value - the same output as [`Self::analyze`], and the validity of each output sample,
+    ///   the fine channels of the same coarse channel sharing the same validity
+    pub fn analyze_masked(
+        &mut self,
+        x: ArrayView2<Complex<T>>,
+        mask: ArrayView2<bool>,
+    ) -> (Array2<Complex<T>>, Array2<bool>) {
+        assert_eq!(x.shape(), mask.shape());
+        let nfine = self.nfine_per_coarse();
+        let slots = self.group_slots();
+        let output_length = self.pfb[0].predict_output_length(x.ncols());
+        let mut out_mask =
+            Array2::from_elem((self.coarse_ch_selected.len() * nfine, output_length), true);
+        self.pfb
+            .iter_mut()
+            .zip(self.coarse_ch_selected.iter())
+            .zip(slots.iter())
+            .for_each(|((pfb, &ch), &g)| {
+                let m = pfb.propagate_mask(&mask.row(ch).to_vec());
+                out_mask
+                    .slice_mut(s![g * nfine..(g + 1) * nfine, ..])
+                    .outer_iter_mut()
+                    .for_each(|mut row| row.iter_mut().zip(&m).for_each(|(a, &b)| *a = b));
             });
-        let nch_fine = self.pfb[0].nch();
-        let data_len = x.ncols();
-        let nch_output = self.coarse_ch_selected.len() * nch_fine / 2;
-        let output_length = self.pfb[0].predict_output_length(data_len);
-        let mut result = unsafe { Array2::uninit((nch_output, output_length)).assume_init() };
+        (self.analyze(x), out_mask)
+    }
 
-        //self.coarse_ch_selected.iter().into_par_iter();
-        result
-            .axis_chunks_iter_mut(Axis(0), nch_fine / 2)
-            .zip(self.pfb.iter_mut())
-            .zip(x.axis_iter(Axis(0)))
-            .for_each(|((mut r, pfb), x1)| {
-                let y = pfb.analyze(x1.as_slice().unwrap());
-                let y = fftshift2(y.view());
-                r.assign(&y.slice(s![nch_fine / 4..nch_fine / 4 * 3, ..]));
+    /// Further channelize the input coarse channels into a caller-provided buffer
+    /// * `x` - input coarse channels, 2D array view, with `number of coarse channels` rows.
+    /// * `out` - output buffer, the shape of which should be `layout.shape(number of fine channels, predict_output_length(x.ncols()))`
+    /// * `layout` - whether `out` is channel-major, as the output of [`Self::analyze`], or time-major
+    pub fn analyze_into(
+        &mut self,
+        x: ArrayView2<Complex<T>>,
+        mut out: ArrayViewMut2<Complex<T>>,
+        layout: DataLayout,
+    ) {
+        let nfine = self.nfine_per_coarse();
+        let fine_index: Vec<_> = (0..nfine).map(|r| self.fine_index(r)).collect();
+        let slots = self.group_slots();
+        let output_length = self.pfb[0].predict_output_length(x.ncols());
+        assert_eq!(
+            out.shape(),
+            &layout.shape(self.coarse_ch_selected.len() * nfine, output_length)
+        );
+        let (frame_axis, ch_axis) = (layout.frame_axis(), layout.channel_axis());
+
+        self.phase.clear();
+        self.phase
+            .extend((0..x.ncols()).map(|_| self.shifter.get()));
+
+        self.pfb
+            .iter_mut()
+            .zip(self.coarse_ch_selected.iter())
+            .zip(slots.iter())
+            .for_each(|((pfb, &ch), &g)| {
+                self.coarse_input.clear();
+                self.coarse_input
+                    .extend(x.row(ch).iter().zip(&self.phase).map(|(&a, &f)| a * f));
+                let mut out_g =
+                    out.slice_axis_mut(ch_axis, Slice::from(g * nfine..(g + 1) * nfine));
+                pfb.for_each_frame(&self.coarse_input, |i, y| {
+                    out_g
+                        .index_axis_mut(frame_axis, i)
+                        .iter_mut()
+                        .zip(&fine_index)
+                        .for_each(|(a, &j)| *a = y[j]);
+                });
             });
-        //println!("{:?} {}", result.shape(), nch_fine);
-        result
     }
 
     #[allow(clippy::unused_enumerate_index)]
@@ -116,24 +422,278 @@ where
                     *c1 *= f;
                 })
             });
-        let nch_fine = self.pfb[0].nch();
+        let nfine = self.nfine_per_coarse();
+        let fine_index: Vec<_> = (0..nfine).map(|r| self.fine_index(r)).collect();
+        let slots = self.group_slots();
         let data_len = x.ncols();
-        let nch_output = self.coarse_ch_selected.len() * nch_fine / 2;
+        let nch_output = self.coarse_ch_selected.len() * nfine;
         let output_length = self.pfb[0].predict_output_length(data_len);
-        let mut result = unsafe { Array2::uninit((nch_output, output_length)).assume_init() };
+        let mut result = Array2::<Complex<T>>::default((nch_output, output_length));
 
-        //self.coarse_ch_selected.iter().into_par_iter();
-        result
-            .axis_chunks_iter_mut(Axis(0), nch_fine / 2)
-            .into_par_iter()
-            .zip_eq(self.pfb.par_iter_mut())
+        let y: Vec<_> = self
+            .pfb
+            .par_iter_mut()
             .zip_eq(x.axis_iter(Axis(0)).into_par_iter())
-            .for_each(|((mut r, pfb), x1)| {
-                let y = pfb.analyze_par(x1.as_slice().unwrap());
-                let y = fftshift2(y.view());
-                r.assign(&y.slice(s![nch_fine / 4..nch_fine / 4 * 3, ..]));
-            });
-        //println!("{:?} {}", result.shape(), nch_fine);
+            .map(|(pfb, x1)| pfb.analyze_par(x1.as_slice().unwrap()))
+            .collect();
+        y.iter().zip(slots.iter()).for_each(|(y, &g)| {
+            result
+                .slice_mut(s![g * nfine..(g + 1) * nfine, ..])
+                .assign(&y.select(Axis(0), &fine_index));
+        });
         result
     }
 }
+
+impl<T> Channelizer for CspPfb<T>
+where
+    T: Copy
+        + Float
+        + FloatConst
+        + std::ops::MulAssign<T>
+        + ScalarOperand
+        + NumAssign
+        + std::fmt::Debug
+        + Sync
+        + Send
+        + FftNum,
+    Complex<T>: Copy
+        + Add<Complex<T>, Output = Complex<T>>
+        + Mul<T, Output = Complex<T>>
+        + Mul<Complex<T>, Output = Complex<T>>
+        + Sum
+        + Default
+        + ScalarOperand
+        + Sync,
+{
+    type Input = Array2<Complex<T>>;
+    type Output = Complex<T>;
+
+    fn nch(&self) -> usize {
+        self.coarse_ch_selected.len() * self.nfine_per_coarse()
+    }
+
+    fn output_layout(&self) -> OutputLayout {
+        OutputLayout::CoarseGrouped {
+            nfine_per_coarse: self.nfine_per_coarse(),
+        }
+    }
+
+    fn predict_output_length(&self, input_len: usize) -> usize {
+        self.pfb[0].predict_output_length(input_len)
+    }
+
+    fn timing(&self) -> Timing {
+        CspPfb::timing(self)
+    }
+
+    fn group_delay(&self) -> GroupDelay {
+        CspPfb::group_delay(self)
+    }
+
+    fn analyze(&mut self, input: &Array2<Complex<T>>) -> Array2<Complex<T>> {
+        CspPfb::analyze(self, input.view())
+    }
+
+    fn analyze_par(&mut self, input: &Array2<Complex<T>>) -> Array2<Complex<T>> {
+        CspPfb::analyze_par(self, input.view())
+    }
+
+    fn analyze_into(
+        &mut self,
+        input: &Array2<Complex<T>>,
+        out: ArrayViewMut2<Complex<T>>,
+        layout: DataLayout,
+    ) {
+        CspPfb::analyze_into(self, input.view(), out, layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ampl_resp::ampl_resp_2stages_1freq_with, channelizer::Channelizer, ospfb, ospfb2,
+        windowed_fir::pfb_coeff,
+    };
+
+    #[test]
+    fn stitching_test() {
+        let nch_coarse = 16;
+        let nch_fine = 32;
+        let coeff_coarse = pfb_coeff::<f64>(nch_coarse / 2, 12, 1.1);
+        let coeff_fine = pfb_coeff::<f64>(nch_fine, 8, 1.1);
+        let fine_pfb =
+            cspfb::Analyzer::<Complex<f64>, f64>::new(nch_fine, coeff_fine.as_slice().unwrap());
+        let nfine = nch_fine / 2;
+
+        let mut last_peak = 0;
+        (0..40).for_each(|i| {
+            // from -1.4 to 1.4 coarse channels
+            let f = -1.4 + 2.8 * i as f64 / 39.0;
+            let mut coarse_pfb = ospfb::Analyzer::<Complex<f64>, f64>::new(
+                nch_coarse,
+                coeff_coarse.as_slice().unwrap(),
+            );
+            let mut csp = CspPfb::new(&[1, 15, 0], &fine_pfb).with_stitching(nch_coarse);
+            let (_, fine_resp) = ampl_resp_2stages_1freq_with(
+                &mut coarse_pfb,
+                &mut csp,
+                f * 2.0 * f64::PI() / nch_coarse as f64,
+                nch_fine * nch_coarse * 4,
+                2,
+            );
+            let peak = fine_resp
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+                .unwrap()
+                .0;
+            let expected = (f + 1.5) * nfine as f64;
+            assert!((peak as f64 - expected).abs() <= 1.0);
+            assert!(peak >= last_peak);
+            last_peak = peak;
+        });
+    }
+
+    #[test]
+    fn retention_test() {
+        let nch_fine = 16;
+        let nfine = 12;
+        let coeff_fine = pfb_coeff::<f64>(nch_fine, 8, 1.1);
+        let fine_pfb =
+            cspfb::Analyzer::<Complex<f64>, f64>::new(nch_fine, coeff_fine.as_slice().unwrap());
+        let x = Array2::from_shape_vec(
+            (4, nch_fine * 20),
+            cspfb::tests::white_noise(4 * nch_fine * 20, 5),
+        )
+        .unwrap();
+        let mut csp1 = CspPfb::new(&[3, 0, 2], &fine_pfb).with_nfine_per_coarse(nfine);
+        let mut csp2 = CspPfb::new(&[3, 0, 2], &fine_pfb)
+            .with_nfine_per_coarse(nfine)
+            .with_stitching(4);
+        let mut csp3 = CspPfb::new(&[3, 0, 2], &fine_pfb)
+            .with_nfine_per_coarse(nfine)
+            .with_stitching(4);
+        let y1 = csp1.analyze(x.view());
+        let y2 = csp2.analyze(x.view());
+        let y3 = csp3.analyze_par(x.view());
+        assert_eq!(y1.shape(), &[3 * nfine, 20]);
+        // coarse channels 2 and 3 are at negative frequencies -2 and -1
+        let order = [2, 0, 1];
+        order.iter().enumerate().for_each(|(slot, &g)| {
+            assert_eq!(
+                y2.slice(s![slot * nfine..(slot + 1) * nfine, ..]),
+                y1.slice(s![g * nfine..(g + 1) * nfine, ..])
+            );
+        });
+        assert!(y2
+            .iter()
+            .zip(y3.iter())
+            .all(|(a, b)| (a - b).norm() < 1e-12));
+    }
+
+    /// the fine channel with the largest response to a tone should be the one labelled with the nearest frequency
+    fn check_frequencies<C, F>(make_coarse: F, coarse_freqs: &[ChannelFrequency<f64>])
+    where
+        C: Channelizer<Input = [Complex<f64>], Output = Complex<f64>>,
+        F: Fn() -> C,
+    {
+        let nch_fine = 16;
+        let coeff_fine = pfb_coeff::<f64>(nch_fine, 8, 1.1);
+        let fine_pfb =
+            cspfb::Analyzer::<Complex<f64>, f64>::new(nch_fine, coeff_fine.as_slice().unwrap());
+        let nch_coarse = coarse_freqs.len();
+        let csp = CspPfb::new(&[1, nch_coarse - 1, 0], &fine_pfb).with_stitching(nch_coarse);
+        let fine_freqs = csp.channel_frequencies(coarse_freqs);
+        [-0.09, -0.07, -0.031, 0.0, 0.013, 0.05, 0.08]
+            .iter()
+            .for_each(|&f| {
+                let mut coarse_pfb = make_coarse();
+                let mut csp =
+                    CspPfb::new(&[1, nch_coarse - 1, 0], &fine_pfb).with_stitching(nch_coarse);
+                let (_, fine_resp) = ampl_resp_2stages_1freq_with(
+                    &mut coarse_pfb,
+                    &mut csp,
+                    f * 2.0 * f64::PI(),
+                    nch_fine * nch_coarse * 4,
+                    2,
+                );
+                let peak = fine_resp
+                    .iter()
+                    .enumerate()
+                    .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+                    .unwrap()
+                    .0;
+                assert!((fine_freqs[peak].center - f).abs() <= fine_freqs[peak].bandwidth / 2.0);
+            });
+    }
+
+    #[test]
+    fn channel_frequencies_test() {
+        let nch_coarse = 16;
+        let coeff = pfb_coeff::<f64>(nch_coarse / 2, 12, 1.1);
+        let coarse_pfb =
+            || ospfb::Analyzer::<Complex<f64>, f64>::new(nch_coarse, coeff.as_slice().unwrap());
+        check_frequencies(coarse_pfb, &coarse_pfb().normalized_channel_frequencies());
+
+        let coeff = pfb_coeff::<f64>(nch_coarse, 12, 1.1 * 1.55);
+        let coarse_pfb =
+            || ospfb2::Analyzer::<Complex<f64>, f64>::new(nch_coarse, coeff.as_slice().unwrap());
+        check_frequencies(coarse_pfb, &coarse_pfb().normalized_channel_frequencies());
+    }
+
+    #[test]
+    fn reselection_test() {
+        let nch_fine = 16;
+        let nfine = nch_fine / 2;
+        let coeff_fine = pfb_coeff::<f64>(nch_fine, 4, 1.1);
+        let fine_pfb =
+            cspfb::Analyzer::<Complex<f64>, f64>::new(nch_fine, coeff_fine.as_slice().unwrap());
+        let x = Array2::from_shape_vec(
+            (4, nch_fine * 40),
+            cspfb::tests::white_noise(4 * nch_fine * 40, 7),
+        )
+        .unwrap();
+        let n1 = nch_fine * 10 + 5;
+        let mut csp = CspPfb::new(&[0, 1, 2], &fine_pfb);
+        // references, which have been selecting the same coarse channels from the beginning
+        let mut csp0 = CspPfb::new(&[0], &fine_pfb);
+        let mut csp3 = CspPfb::new(&[3], &fine_pfb);
+        let mut csp1 = CspPfb::new(&[1], &fine_pfb);
+
+        let y = csp.analyze(x.slice(s![.., ..n1]));
+        assert_eq!(y.shape(), &[3 * nfine, 10]);
+        csp.remove_coarse_ch(1);
+        let ntransient3 = csp.replace_coarse_ch(2, 3);
+        let ntransient1 = csp.add_coarse_ch(1);
+        assert_eq!(csp.coarse_ch_selected(), &[0, 3, 1]);
+        assert_eq!(ntransient3, 4);
+        assert_eq!(ntransient1, 4);
+        let y = csp.analyze(x.slice(s![.., n1..]));
+        assert_eq!(y.shape(), &[3 * nfine, 30]);
+
+        let y0 = csp0.analyze(x.view());
+        let y3 = csp3.analyze(x.view());
+        let y1 = csp1.analyze(x.view());
+        // the kept coarse channel is not affected
+        assert!(y
+            .slice(s![..nfine, ..])
+            .iter()
+            .zip(y0.slice(s![.., 10..]).iter())
+            .all(|(a, b)| (a - b).norm() < 1e-12));
+        // the new coarse channels are the same as the references after the transient
+        assert!(y
+            .slice(s![nfine..2 * nfine, ntransient3..])
+            .iter()
+            .zip(y3.slice(s![.., 10 + ntransient3..]).iter())
+            .all(|(a, b)| (a - b).norm() < 1e-12));
+        assert!(y
+            .slice(s![2 * nfine.., ntransient1..])
+            .iter()
+            .zip(y1.slice(s![.., 10 + ntransient1..]).iter())
+            .all(|(a, b)| (a - b).norm() < 1e-12));
+        // but not before that
+        assert!((y[(nfine, ntransient3 - 1)] - y3[(0, 10 + ntransient3 - 1)]).norm() > 1e-6);
+    }
+}
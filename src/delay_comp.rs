@@ -0,0 +1,247 @@
+//! Delay compensation in the channel domain, i.e., applied to the output of a [`crate::channelizer::Channelizer`],
+//! so that the coarse delay can be compensated in the time domain with [`crate::frac_delayer::FracDelayer`],
+//! and the residual delay after the channelization, together with its rate and the fringe of the local oscillator,
+//! can be compensated per channel as a phase rotation
+
+use crate::channelizer::{ChannelFrequency, DataLayout};
+use ndarray::{parallel::prelude::*, ArrayViewMut2};
+use num::{
+    complex::Complex,
+    traits::{Float, FloatConst},
+};
+use serde::{Deserialize, Serialize};
+
+/// Delay model of one chunk of channelized data
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DelayModel<T> {
+    /// reference time in seconds, counted from the first frame that has been compensated
+    pub t0: T,
+    /// residual delay at `t0`, in seconds
+    pub delay: T,
+    /// rate of the residual delay, in seconds per second
+    pub delay_rate: T,
+    /// extra fringe phase, in radians
+    pub fringe_phase: T,
+}
+
+impl<T> DelayModel<T>
+where
+    T: Float,
+{
+    /// the residual delay at time `t`
+    pub fn delay_at(&self, t: T) -> T {
+        self.delay + self.delay_rate * (t - self.t0)
+    }
+}
+
+/// Delay compensator working on channelized data.
+/// Channel `k` of the frame at time `t` is multiplied by `exp(i*(2*pi*(f_k+lo_freq)*tau(t)+fringe_phase))`,
+/// where `f_k` is the center frequency of the channel relative to the DC of the input signal,
+/// and `tau(t)` is given by [`DelayModel`]. I.e., a delay `tau` of the sky signal is compensated
+/// both for the baseband part and for the fringe due to the local oscillator.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DelayCompensator<T> {
+    /// frequencies of the channels, one for each channel of the data to be compensated,
+    /// the center frequencies of which are relative to the DC of the input signal,
+    /// e.g., returned by `channel_frequencies(sample_rate, 0)` of the analyzer
+    pub freqs: Vec<ChannelFrequency<T>>,
+    /// frequency of the local oscillator, i.e., the sky frequency of the DC of the input signal
+    pub lo_freq: T,
+    /// number of frames that have been compensated
+    pub nframes: usize,
+}
+
+impl<T> DelayCompensator<T>
+where
+    T: Float + FloatConst,
+{
+    /// constructor
+    /// * `freqs` - frequencies of the channels, see [`Self::freqs`]
+    /// * `lo_freq` - frequency of the local oscillator
+    /// ```
+    /// extern crate rsdsp;
+    /// use num::complex::Complex;
+    /// use rsdsp::{
+    ///     windowed_fir
+    ///     , cspfb::Analyzer
+    ///     , delay_comp::{DelayCompensator, DelayModel}
+    ///     , channelizer::DataLayout
+    /// };
+    ///
+    /// let nch=16;
+    /// let coeff=windowed_fir::pfb_coeff::<f64>(nch, 8, 1.1);
+    /// let mut pfb=Analyzer::<Complex<f64>, f64>::new(nch, coeff.as_slice().unwrap());
+    /// let mut comp=DelayCompensator::new(pfb.channel_frequencies(16e6, 0.0), 1e9);
+    /// let model=DelayModel{t0: 0.0, delay: 1e-9, delay_rate: 0.0, fringe_phase: 0.0};
+    /// let mut y=pfb.analyze(&vec![Complex::<f64>::new(1.0, 0.0); nch*10]);
+    /// comp.apply(y.view_mut(), DataLayout::ChannelMajor, &model);
+    /// assert_eq!(comp.nframes, 10);
+    /// ```
+    pub fn new(freqs: Vec<ChannelFrequency<T>>, lo_freq: T) -> Self {
+        Self {
+            freqs,
+            lo_freq,
+            nframes: 0,
+        }
+    }
+
+    /// number of channels
+    pub fn nch(&self) -> usize {
+        self.freqs.len()
+    }
+
+    /// time of the `i`-th frame of channel `k`, in seconds, the group delay of the filter bank is not included
+    pub fn frame_time(&self, k: usize, i: usize) -> T {
+        T::from(i).unwrap() / self.freqs[k].sample_rate
+    }
+
+    /// phase factor to be multiplied to channel `k` of the `i`-th frame
+    pub fn phase_factor(&self, k: usize, i: usize, model: &DelayModel<T>) -> Complex<T> {
+        let two = T::one() + T::one();
+        let tau = model.delay_at(self.frame_time(k, i));
+        let phase =
+            two * T::PI() * (self.freqs[k].center + self.lo_freq) * tau + model.fringe_phase;
+        Complex::from_polar(T::one(), phase)
+    }
+
+    /// compensate the delay of the next chunk of channelized data in place
+    /// * `x` - channelized data, the channels of which are in the same order as [`Self::freqs`]
+    /// * `layout` - memory layout of `x`
+    /// * `model` - delay model
+    pub fn apply(
+        &mut self,
+        mut x: ArrayViewMut2<Complex<T>>,
+        layout: DataLayout,
+        model: &DelayModel<T>,
+    ) {
+        assert_eq!(x.len_of(layout.channel_axis()), self.nch());
+        let nframes = x.len_of(layout.frame_axis());
+        x.axis_iter_mut(layout.channel_axis())
+            .enumerate()
+            .for_each(|(k, mut row)| {
+                row.iter_mut().enumerate().for_each(|(i, x1)| {
+                    *x1 = *x1 * self.phase_factor(k, self.nframes + i, model);
+                })
+            });
+        self.nframes += nframes;
+    }
+
+    /// parallel version of [`Self::apply`]
+    pub fn apply_par(
+        &mut self,
+        mut x: ArrayViewMut2<Complex<T>>,
+        layout: DataLayout,
+        model: &DelayModel<T>,
+    ) where
+        T: Send + Sync,
+    {
+        assert_eq!(x.len_of(layout.channel_axis()), self.nch());
+        let nframes = x.len_of(layout.frame_axis());
+        let this = &*self;
+        x.axis_iter_mut(layout.channel_axis())
+            .into_par_iter()
+            .enumerate()
+            .for_each(|(k, mut row)| {
+                row.iter_mut().enumerate().for_each(|(i, x1)| {
+                    *x1 = *x1 * this.phase_factor(k, this.nframes + i, model);
+                })
+            });
+        self.nframes += nframes;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cspfb, ospfb, windowed_fir::pfb_coeff};
+    use ndarray::{Array2, Axis};
+
+    /// a tone at `freq` delayed by `tau(t)`, mixed down by `lo_freq`
+    fn delayed_tone<F>(
+        n: usize,
+        sample_rate: f64,
+        freq: f64,
+        lo_freq: f64,
+        tau: F,
+    ) -> Vec<Complex<f64>>
+    where
+        F: Fn(f64) -> f64,
+    {
+        (0..n)
+            .map(|i| {
+                let t = i as f64 / sample_rate;
+                Complex::from_polar(1.0, -2.0 * f64::PI() * (freq * tau(t) + lo_freq * tau(t)))
+                    * Complex::from_polar(1.0, 2.0 * f64::PI() * freq * t)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn compensate_test() {
+        let nch = 16;
+        let sample_rate = 16e6;
+        let lo_freq = 1.2e9;
+        // at the center of the channel 3
+        let freq = 3e6;
+        let coeff = pfb_coeff::<f64>(nch / 2, 12, 1.1);
+        let mut pfb_ref = ospfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff.as_slice().unwrap());
+        let mut pfb = ospfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff.as_slice().unwrap());
+        let model = DelayModel {
+            t0: 1e-4,
+            delay: 3.3e-9,
+            delay_rate: 1e-9,
+            fringe_phase: 0.0,
+        };
+        let n = nch * 1000;
+        let x_ref = delayed_tone(n, sample_rate, freq, lo_freq, |_| 0.0);
+        let x = delayed_tone(n, sample_rate, freq, lo_freq, |t| model.delay_at(t));
+        let y_ref = pfb_ref.analyze(&x_ref);
+        let mut comp = DelayCompensator::new(pfb.channel_frequencies(sample_rate, 0.0), lo_freq);
+        let y: Vec<Array2<_>> = x
+            .chunks(nch * 100)
+            .map(|x1| {
+                let mut y = pfb.analyze(x1);
+                comp.apply(y.view_mut(), DataLayout::ChannelMajor, &model);
+                y
+            })
+            .collect();
+        let y =
+            ndarray::concatenate(Axis(1), &y.iter().map(|y| y.view()).collect::<Vec<_>>()).unwrap();
+        assert_eq!(comp.nframes, y.ncols());
+        // skip the transient of the filter
+        y.row(3)
+            .iter()
+            .zip(y_ref.row(3).iter())
+            .skip(20)
+            .for_each(|(a, b)| assert!((a - b).norm() < 1e-3 * b.norm(), "{a} {b}"));
+    }
+
+    #[test]
+    fn layout_test() {
+        let nch = 8;
+        let coeff = pfb_coeff::<f64>(nch, 8, 1.1);
+        let pfb = cspfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff.as_slice().unwrap());
+        let freqs = pfb.channel_frequencies(8e6, 0.0);
+        let model = DelayModel {
+            t0: 0.0,
+            delay: 1e-8,
+            delay_rate: 1e-3,
+            fringe_phase: 0.3,
+        };
+        let x = Array2::from_shape_fn((nch, 50), |(i, j)| Complex::new(i as f64, j as f64));
+        let mut y1 = x.clone();
+        let mut y2 = x.t().to_owned();
+        let mut y3 = x.clone();
+        let mut comp1 = DelayCompensator::new(freqs.clone(), 1e8);
+        let mut comp2 = comp1.clone();
+        let mut comp3 = comp1.clone();
+        comp1.apply(y1.view_mut(), DataLayout::ChannelMajor, &model);
+        comp2.apply(y2.view_mut(), DataLayout::TimeMajor, &model);
+        comp3.apply_par(y3.view_mut(), DataLayout::ChannelMajor, &model);
+        assert!(y1
+            .iter()
+            .zip(y2.t().iter())
+            .chain(y1.iter().zip(y3.iter()))
+            .all(|(a, b)| (a - b).norm() < 1e-12));
+    }
+}
@@ -8,13 +8,18 @@ use std::{
     ops::{Add, Mul},
 };
 
-use crate::windowed_fir::coeff;
+use crate::{
+    timing::{GroupDelay, Timing},
+    windowed_fir::coeff,
+};
 
 pub struct DownSampler<U, T> {
     pub coeff_rev: Vec<T>,
     /// filter state
     pub initial_state: Vec<U>,
     down_sample_ratio: usize,
+    /// number of samples that have been output
+    noutput: usize,
 }
 
 impl<U, T> DownSampler<U, T>
@@ -37,9 +42,35 @@ where
             coeff_rev: c,
             initial_state: vec![U::zero(); tap - 1],
             down_sample_ratio,
+            noutput: 0,
+        }
+    }
+
+    /// timing of the output, i.e., output `j` is at `j*down_sample_ratio-(tap-1)/2` input samples,
+    /// assuming the filter is of linear phase
+    /// ```
+    /// extern crate rsdsp;
+    /// use rsdsp::down_sample::DownSampler;
+    ///
+    /// let mut ds=DownSampler::<f64, f64>::new(33, 4);
+    /// let y=ds.downsample(&vec![1.0; 100]);
+    /// assert_eq!(ds.timing().next_output, y.len());
+    /// assert_eq!(ds.timing().position(0), -16.0);
+    /// assert_eq!(ds.timing().step, 4.0);
+    /// ```
+    pub fn timing(&self) -> Timing {
+        Timing {
+            next_output: self.noutput,
+            offset: -(self.coeff_rev.len() as f64 - 1.0) / 2.0,
+            step: self.down_sample_ratio as f64,
         }
     }
 
+    /// group delay, i.e., `(tap-1)/2` input samples
+    pub fn group_delay(&self) -> GroupDelay {
+        GroupDelay::fir(self.coeff_rev.len(), self.down_sample_ratio as f64)
+    }
+
     pub fn downsample(&mut self, input: &[U]) -> Vec<U> {
         self.initial_state.extend_from_slice(input);
         let tap = self.coeff_rev.len();
@@ -59,6 +90,7 @@ where
             })
             .collect();
         self.initial_state = new_init_state;
+        self.noutput += noutput;
         result
     }
 }
@@ -1,5 +1,8 @@
-//! A module containing FIR filter
+//! A module containing FIR filter, filtered either directly or with fft
 
+use crate::{timing::GroupDelay, utils::FftPlan};
+use num::{complex::Complex, traits::Float};
+use rustfft::FftNum;
 use serde::{Deserialize, Serialize};
 
 use std::{
@@ -7,6 +10,9 @@ use std::{
     ops::{Add, Mul},
 };
 
+/// min number of taps for [`AutoFilter::new`] to filter with fft
+pub const FFT_FILTER_MIN_TAP: usize = 64;
+
 /// FIR filter
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Filter<U, T> {
@@ -31,6 +37,11 @@ where
         }
     }
 
+    /// group delay, i.e., `(tap-1)/2` samples, assuming the filter is of linear phase
+    pub fn group_delay(&self) -> GroupDelay {
+        GroupDelay::fir(self.coeff_rev.len(), 1.0)
+    }
+
     /// set the initial state
     pub fn with_initial_state(mut self, initial_state: Vec<U>) -> Self {
         assert!(initial_state.len() == self.coeff_rev.len() - 1);
@@ -84,3 +95,407 @@ where
         //assert_eq!(self.initial_state.len(), tap - 1);
     }
 }
+
+/// samples that can be filtered by [`FftFilter`], i.e., real or complex
+pub trait FftSample<T>: Copy + Default
+where
+    T: Float,
+{
+    /// convert to complex for the fft
+    fn to_complex(self) -> Complex<T>;
+    /// convert back from the output of the inverse fft
+    fn from_complex(x: Complex<T>) -> Self;
+}
+
+impl<T> FftSample<T> for T
+where
+    T: Float + Default,
+{
+    fn to_complex(self) -> Complex<T> {
+        Complex::new(self, T::zero())
+    }
+
+    fn from_complex(x: Complex<T>) -> Self {
+        x.re
+    }
+}
+
+impl<T> FftSample<T> for Complex<T>
+where
+    T: Float + Default,
+{
+    fn to_complex(self) -> Complex<T> {
+        self
+    }
+
+    fn from_complex(x: Complex<T>) -> Self {
+        x
+    }
+}
+
+/// serialized form of [`FftFilter`]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct FftFilterDesc<U, T> {
+    coeff_rev: Vec<T>,
+    initial_state: Vec<U>,
+    /// the same as that of [`FftFilter::new`] if missing
+    #[serde(default)]
+    fft_len: Option<usize>,
+}
+
+/// FIR filter with the overlap-save method, which gives the same output as [`Filter`],
+/// but costs `O(log(tap))` rather than `O(tap)` operations per sample.
+/// It is serialized as [`Filter`] together with the fft length, the spectrum of the filter being computed again when deserialized,
+/// so that a serialized [`Filter`] can also be loaded as an [`FftFilter`] with the fft length of [`FftFilter::new`].
+///
+/// Both real and complex samples go through a complex fft of `fft_len`,
+/// because [`crate::utils::RealFftPlan`] only transforms forward, while the inverse transform of the filtered spectrum is also needed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "FftFilterDesc<U, T>", into = "FftFilterDesc<U, T>")]
+pub struct FftFilter<U, T>
+where
+    T: FftNum + Float,
+    U: FftSample<T>,
+{
+    /// reversed coefficients, i.e., impulse respone
+    coeff_rev: Vec<T>,
+    /// filter state, i.e., the last `tap-1` input samples
+    pub initial_state: Vec<U>,
+    /// spectrum of the filter, normalized by the fft length
+    kernel: Vec<Complex<T>>,
+    fft: FftPlan<T>,
+    ifft: FftPlan<T>,
+    /// one block of `fft_len` samples, reused by each call
+    buffer: Vec<Complex<T>>,
+}
+
+impl<U, T> FftFilter<U, T>
+where
+    T: FftNum + Float,
+    U: FftSample<T>,
+{
+    /// construct a FIR with its coefficients, the fft length being the power of 2 no less than `4*tap`
+    pub fn new(coeff: Vec<T>) -> Self {
+        let fft_len = default_fft_len(coeff.len());
+        Self::with_fft_len(coeff, fft_len)
+    }
+
+    /// construct a FIR with its coefficients and the fft length,
+    /// each fft producing `fft_len+1-tap` output samples
+    /// ```
+    /// extern crate rsdsp;
+    /// use rsdsp::filter::{FftFilter, Filter};
+    ///
+    /// let coeff: Vec<f64> = (0..100).map(|i| (i as f64 * 0.1).sin()).collect();
+    /// let signal: Vec<f64> = (0..1000).map(|i| (i as f64 * 0.37).cos()).collect();
+    /// let mut filter = Filter::new(coeff.clone());
+    /// let mut fft_filter = FftFilter::with_fft_len(coeff, 256);
+    /// for x in signal.chunks(300) {
+    ///     let y1 = filter.filter(x);
+    ///     let y2 = fft_filter.filter(x);
+    ///     assert!(y1.iter().zip(&y2).all(|(a, b)| (a - b).abs() < 1e-10));
+    /// }
+    /// ```
+    pub fn with_fft_len(coeff: Vec<T>, fft_len: usize) -> Self {
+        let tap = coeff.len();
+        let mut coeff_rev = coeff;
+        coeff_rev.reverse();
+        Self::try_from(FftFilterDesc {
+            coeff_rev,
+            initial_state: vec![U::default(); tap.saturating_sub(1)],
+            fft_len: Some(fft_len),
+        })
+        .unwrap()
+    }
+
+    /// reversed coefficients, i.e., impulse respone
+    pub fn coeff_rev(&self) -> &[T] {
+        &self.coeff_rev
+    }
+
+    /// length of the fft
+    pub fn fft_len(&self) -> usize {
+        self.fft.len()
+    }
+
+    /// group delay, i.e., `(tap-1)/2` samples, assuming the filter is of linear phase
+    pub fn group_delay(&self) -> GroupDelay {
+        GroupDelay::fir(self.coeff_rev.len(), 1.0)
+    }
+
+    /// set the initial state
+    pub fn with_initial_state(mut self, initial_state: Vec<U>) -> Self {
+        assert!(initial_state.len() == self.coeff_rev.len() - 1);
+        self.initial_state = initial_state;
+        self
+    }
+
+    /// filter a time series signal
+    /// return the filtered signal
+    pub fn filter(&mut self, signal: &[U]) -> Vec<U> {
+        let tap = self.coeff_rev.len();
+        let block_len = self.fft.len() + 1 - tap;
+        let mut result = Vec::with_capacity(signal.len());
+        for x in signal.chunks(block_len) {
+            // the samples wrapped around by the circular convolution are all before the output ones
+            let nvalid = tap - 1 + x.len();
+            for (a, &b) in self
+                .buffer
+                .iter_mut()
+                .zip(self.initial_state.iter().chain(x))
+            {
+                *a = b.to_complex();
+            }
+            self.buffer[nvalid..].fill(Complex::new(T::zero(), T::zero()));
+            self.fft.process(&mut self.buffer);
+            self.buffer
+                .iter_mut()
+                .zip(&self.kernel)
+                .for_each(|(a, &b)| *a = *a * b);
+            self.ifft.process(&mut self.buffer);
+            result.extend(
+                self.buffer[tap - 1..nvalid]
+                    .iter()
+                    .map(|&y| U::from_complex(y)),
+            );
+            self.feed(x);
+        }
+        result
+    }
+
+    /// only feed the data and update the internal state
+    /// do not perform the filtering
+    pub fn feed(&mut self, signal: &[U]) {
+        self.initial_state.extend_from_slice(signal);
+        self.initial_state.drain(..signal.len());
+    }
+}
+
+/// the power of 2 no less than `4*tap`
+fn default_fft_len(tap: usize) -> usize {
+    (4 * tap).next_power_of_two()
+}
+
+impl<U, T> TryFrom<FftFilterDesc<U, T>> for FftFilter<U, T>
+where
+    T: FftNum + Float,
+    U: FftSample<T>,
+{
+    type Error = String;
+
+    fn try_from(desc: FftFilterDesc<U, T>) -> Result<Self, Self::Error> {
+        let tap = desc.coeff_rev.len();
+        if tap == 0 {
+            return Err("a filter should have at least one tap".to_string());
+        }
+        let fft_len = desc.fft_len.unwrap_or_else(|| default_fft_len(tap));
+        if fft_len == 0 {
+            return Err("fft length should be positive".to_string());
+        }
+        if fft_len < tap {
+            return Err(format!(
+                "fft length {fft_len} should be no less than the number of taps {tap}"
+            ));
+        }
+        if desc.initial_state.len() != tap - 1 {
+            return Err(format!(
+                "initial state has {} samples, while a filter of {tap} taps needs {}",
+                desc.initial_state.len(),
+                tap - 1
+            ));
+        }
+        let norm = T::from(fft_len).unwrap();
+        let mut fft = FftPlan::forward(fft_len);
+        let mut kernel: Vec<_> = desc
+            .coeff_rev
+            .iter()
+            .rev()
+            .map(|&x| Complex::new(x / norm, T::zero()))
+            .chain(std::iter::repeat(Complex::new(T::zero(), T::zero())))
+            .take(fft_len)
+            .collect();
+        fft.process(&mut kernel);
+        Ok(Self {
+            coeff_rev: desc.coeff_rev,
+            initial_state: desc.initial_state,
+            kernel,
+            fft,
+            ifft: FftPlan::inverse(fft_len),
+            buffer: vec![Complex::new(T::zero(), T::zero()); fft_len],
+        })
+    }
+}
+
+impl<U, T> From<FftFilter<U, T>> for FftFilterDesc<U, T>
+where
+    T: FftNum + Float,
+    U: FftSample<T>,
+{
+    fn from(filter: FftFilter<U, T>) -> Self {
+        Self {
+            fft_len: Some(filter.fft.len()),
+            coeff_rev: filter.coeff_rev,
+            initial_state: filter.initial_state,
+        }
+    }
+}
+
+/// FIR filter that is filtered directly if it is short, or with fft otherwise
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum AutoFilter<U, T>
+where
+    T: FftNum + Float,
+    U: FftSample<T>,
+{
+    Direct(Filter<U, T>),
+    Fft(FftFilter<U, T>),
+}
+
+impl<U, T> AutoFilter<U, T>
+where
+    T: FftNum + Float,
+    U: FftSample<T> + Add<U, Output = U> + Mul<T, Output = U> + Sum,
+{
+    /// construct a FIR with its coefficients,
+    /// which is filtered with fft if it has no less than [`FFT_FILTER_MIN_TAP`] taps
+    pub fn new(coeff: Vec<T>) -> Self {
+        if coeff.len() >= FFT_FILTER_MIN_TAP {
+            AutoFilter::Fft(FftFilter::new(coeff))
+        } else {
+            AutoFilter::Direct(Filter::new(coeff))
+        }
+    }
+
+    /// group delay, i.e., `(tap-1)/2` samples, assuming the filter is of linear phase
+    pub fn group_delay(&self) -> GroupDelay {
+        match self {
+            AutoFilter::Direct(f) => f.group_delay(),
+            AutoFilter::Fft(f) => f.group_delay(),
+        }
+    }
+
+    /// set the initial state
+    pub fn with_initial_state(self, initial_state: Vec<U>) -> Self {
+        match self {
+            AutoFilter::Direct(f) => AutoFilter::Direct(f.with_initial_state(initial_state)),
+            AutoFilter::Fft(f) => AutoFilter::Fft(f.with_initial_state(initial_state)),
+        }
+    }
+
+    /// filter a time series signal
+    /// return the filtered signal
+    pub fn filter(&mut self, signal: &[U]) -> Vec<U> {
+        match self {
+            AutoFilter::Direct(f) => f.filter(signal),
+            AutoFilter::Fft(f) => f.filter(signal),
+        }
+    }
+
+    /// only feed the data and update the internal state
+    /// do not perform the filtering
+    pub fn feed(&mut self, signal: &[U]) {
+        match self {
+            AutoFilter::Direct(f) => f.feed(signal),
+            AutoFilter::Fft(f) => f.feed(signal),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AutoFilter, FftFilter, Filter, FFT_FILTER_MIN_TAP};
+    use crate::{cspfb::tests::white_noise, windowed_fir};
+    use num::complex::Complex;
+
+    #[test]
+    fn fft_filter_test() {
+        let signal = white_noise(5000, 1);
+        for (tap, fft_len) in [(5, 16), (33, 33), (200, 256), (300, 2048)] {
+            let coeff = windowed_fir::coeff::<f64>(tap, 0.3);
+            let mut filter = Filter::<Complex<f64>, f64>::new(coeff.clone());
+            let mut fft_filter = FftFilter::<Complex<f64>, f64>::with_fft_len(coeff, fft_len);
+            // chunks both shorter and longer than one block
+            for x in signal.chunks(77).chain(signal.chunks(1500)) {
+                let y1 = filter.filter(x);
+                let y2 = fft_filter.filter(x);
+                assert_eq!(y1.len(), y2.len());
+                assert!(y1.iter().zip(&y2).all(|(a, b)| (a - b).norm() < 1e-12));
+                assert!(filter
+                    .initial_state
+                    .iter()
+                    .zip(&fft_filter.initial_state)
+                    .all(|(a, b)| a == b));
+            }
+        }
+    }
+
+    #[test]
+    fn fft_filter_serde_test() {
+        let signal: Vec<_> = white_noise(2000, 2).iter().map(|x| x.re).collect();
+        let mut filter1 = FftFilter::<f64, f64>::new(windowed_fir::coeff(129, 0.2));
+        filter1.filter(&signal[..300]);
+        // the spectrum and the fft plans are rebuilt after deserialization
+        let mut filter2: FftFilter<f64, f64> =
+            serde_yaml::from_str(&serde_yaml::to_string(&filter1).unwrap()).unwrap();
+        assert_eq!(filter1.fft_len(), filter2.fft_len());
+        let y1 = filter1.filter(&signal[300..]);
+        let y2 = filter2.filter(&signal[300..]);
+        assert!(y1.iter().zip(&y2).all(|(a, b)| (a - b).abs() < 1e-12));
+    }
+
+    #[test]
+    fn filter_to_fft_filter_serde_test() {
+        let signal: Vec<_> = white_noise(2000, 3).iter().map(|x| x.re).collect();
+        let coeff = windowed_fir::coeff(129, 0.2);
+        let mut filter = Filter::<f64, f64>::new(coeff.clone());
+        filter.filter(&signal[..300]);
+        // a serialized `Filter` has no fft length, which defaults to that of `FftFilter::new`
+        let mut fft_filter: FftFilter<f64, f64> =
+            serde_yaml::from_str(&serde_yaml::to_string(&filter).unwrap()).unwrap();
+        assert_eq!(
+            fft_filter.fft_len(),
+            FftFilter::<f64, f64>::new(coeff).fft_len()
+        );
+        let y1 = filter.filter(&signal[300..]);
+        let y2 = fft_filter.filter(&signal[300..]);
+        assert!(y1.iter().zip(&y2).all(|(a, b)| (a - b).abs() < 1e-12));
+    }
+
+    #[test]
+    fn malformed_serde_test() {
+        let filter = FftFilter::<f64, f64>::with_fft_len(vec![1.0, 2.0, 3.0], 4);
+        let yaml = serde_yaml::to_string(&filter).unwrap();
+        assert!(serde_yaml::from_str::<FftFilter<f64, f64>>(&yaml).is_ok());
+        for bad in [
+            yaml.replace("fft_len: 4", "fft_len: 0"),
+            yaml.replace("fft_len: 4", "fft_len: 2"),
+            yaml.replace("initial_state:\n- 0.0\n", "initial_state:\n"),
+        ] {
+            assert_ne!(bad, yaml);
+            assert!(serde_yaml::from_str::<FftFilter<f64, f64>>(&bad).is_err());
+        }
+        assert!(
+            serde_yaml::from_str::<FftFilter<f64, f64>>("coeff_rev: []\ninitial_state: []\n")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn auto_filter_test() {
+        let signal: Vec<_> = white_noise(2000, 3).iter().map(|x| x.re).collect();
+        for tap in [FFT_FILTER_MIN_TAP - 1, FFT_FILTER_MIN_TAP] {
+            let coeff = windowed_fir::coeff::<f64>(tap, 0.2);
+            let mut filter = Filter::<f64, f64>::new(coeff.clone());
+            let mut auto = AutoFilter::<f64, f64>::new(coeff);
+            assert_eq!(
+                matches!(auto, AutoFilter::Fft(_)),
+                tap >= FFT_FILTER_MIN_TAP
+            );
+            for x in signal.chunks(333) {
+                let y1 = filter.filter(x);
+                let y2 = auto.filter(x);
+                assert!(y1.iter().zip(&y2).all(|(a, b)| (a - b).abs() < 1e-12));
+            }
+        }
+    }
+}
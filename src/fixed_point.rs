@@ -0,0 +1,835 @@
+//! Fixed-point poly phase filter banks, which model the integer data path of an FPGA F-engine bit by bit
+
+use crate::{
+    batch_filter::BatchFilter,
+    channelizer::{Channelizer, OutputLayout},
+    timing::{GroupDelay, Timing},
+    utils::polyphase_decomp,
+};
+use ndarray::{Array2, ArrayView2, Axis};
+use num::complex::Complex;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+/// How the discarded lower bits are treated when shifting right
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rounding {
+    /// drop the lower bits, i.e., rounding toward negative infinity
+    Truncate,
+    /// round to the nearest, halves rounded toward positive infinity
+    RoundHalfUp,
+    /// round to the nearest, halves rounded to the even one, i.e., convergent rounding
+    RoundHalfEven,
+}
+
+/// How a value that does not fit into the data width is treated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OverflowMode {
+    /// clamp to the max or min value
+    Saturate,
+    /// keep the lower bits, i.e., two's complement wrapping
+    Wrap,
+}
+
+/// Bit widths and arithmetic modes of the data path
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FixedPointCfg {
+    /// width of the quantized prototype filter coefficients, the max absolute value of which is mapped to `2^(coeff_bits-1)-1`
+    pub coeff_bits: u32,
+    /// width of the data after the filters and after each fft stage, no more than 32
+    pub data_bits: u32,
+    /// width of the twiddle factors, no more than 25
+    pub twiddle_bits: u32,
+    /// number of bits shifted right after the accumulation of the filters
+    pub fir_shift: u32,
+    /// shift schedule of the fft, the `i`-th bit (from the LSB) indicates whether the output of the `i`-th stage is shifted right by 1 bit
+    pub fft_shift: u32,
+    pub rounding: Rounding,
+    pub overflow: OverflowMode,
+}
+
+impl FixedPointCfg {
+    /// check that the bit widths can be handled by the 64-bit data path
+    fn check_widths(&self) {
+        for (name, bits) in [
+            ("coeff_bits", self.coeff_bits),
+            ("data_bits", self.data_bits),
+            ("twiddle_bits", self.twiddle_bits),
+        ] {
+            assert!(
+                (1..=63).contains(&bits),
+                "{name} should be in 1..=63, but is {bits}"
+            );
+        }
+        assert!(self.data_bits <= 32 && self.twiddle_bits <= 25);
+        assert!(self.fir_shift < 64);
+    }
+
+    /// check that the accumulation of `tap` products of the coefficients and `input_bits`-bit samples fits into 64 bits
+    fn check_accumulator(&self, input_bits: u32, tap: usize) {
+        let bits = input_bits + self.coeff_bits + tap.next_power_of_two().trailing_zeros();
+        assert!(
+            bits < 64,
+            "the accumulator of the filters needs {bits} bits, more than 63"
+        );
+    }
+}
+
+/// Number of overflows since the construction or the last [`OverflowCounters::reset`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OverflowCounters {
+    /// overflows of the filter outputs
+    pub fir: u64,
+    /// overflows of each fft stage
+    pub fft: Vec<u64>,
+}
+
+impl OverflowCounters {
+    fn new(nstages: usize) -> Self {
+        Self {
+            fir: 0,
+            fft: vec![0; nstages],
+        }
+    }
+
+    /// total number of overflows
+    pub fn total(&self) -> u64 {
+        self.fir + self.fft.iter().sum::<u64>()
+    }
+
+    pub fn reset(&mut self) {
+        self.fir = 0;
+        self.fft.iter_mut().for_each(|x| *x = 0);
+    }
+}
+
+/// shift `x` right by `s` bits with `rounding`
+pub fn shift_round(x: i64, s: u32, rounding: Rounding) -> i64 {
+    if s == 0 {
+        return x;
+    }
+    let half = 1_i64 << (s - 1);
+    match rounding {
+        Rounding::Truncate => x >> s,
+        Rounding::RoundHalfUp => (x + half) >> s,
+        Rounding::RoundHalfEven => {
+            let q = x >> s;
+            let rem = x - (q << s);
+            if rem > half || (rem == half && q & 1 == 1) {
+                q + 1
+            } else {
+                q
+            }
+        }
+    }
+}
+
+/// fit `x` into a signed integer of `bits` bits, `counter` is increased if it overflows
+pub fn fit_width(x: i64, bits: u32, mode: OverflowMode, counter: &mut u64) -> i64 {
+    let max = (1_i64 << (bits - 1)) - 1;
+    let min = -(1_i64 << (bits - 1));
+    if x >= min && x <= max {
+        return x;
+    }
+    *counter += 1;
+    match mode {
+        OverflowMode::Saturate => x.clamp(min, max),
+        OverflowMode::Wrap => (x << (64 - bits)) >> (64 - bits),
+    }
+}
+
+/// quantize the prototype filter, so that the max absolute value is mapped to `2^(bits-1)-1`
+/// * return value - the quantized coefficients and the scale factor
+pub fn quantize_coeff(coeff: &[f64], bits: u32) -> (Vec<i64>, f64) {
+    let max = coeff.iter().fold(0.0_f64, |a, &b| a.max(b.abs()));
+    let scale = ((1_i64 << (bits - 1)) - 1) as f64 / max;
+    (
+        coeff.iter().map(|&c| (c * scale).round() as i64).collect(),
+        scale,
+    )
+}
+
+/// quantize `exp(i*phi)` to `bits` bits
+fn quantize_phase(phi: f64, bits: u32) -> Complex<i64> {
+    let full = (1_i64 << (bits - 1)) as f64;
+    let max = (1_i64 << (bits - 1)) - 1;
+    let q = |x: f64| ((x * full).round() as i64).clamp(-max, max);
+    Complex::new(q(phi.cos()), q(phi.sin()))
+}
+
+/// Radix-2 decimation-in-time fft on integers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FixedFft {
+    /// `exp(-2*pi*i*k/n)` for `k` in `0..n/2`, quantized
+    twiddle: Vec<Complex<i64>>,
+    /// bit reversed indices
+    bitrev: Vec<usize>,
+}
+
+impl FixedFft {
+    fn new(n: usize, twiddle_bits: u32) -> Self {
+        assert!(n.is_power_of_two() && n >= 2);
+        let nbits = n.trailing_zeros();
+        let twiddle = (0..n / 2)
+            .map(|k| quantize_phase(-2.0 * PI * k as f64 / n as f64, twiddle_bits))
+            .collect();
+        let bitrev = (0..n)
+            .map(|i| i.reverse_bits() >> (usize::BITS - nbits))
+            .collect();
+        Self { twiddle, bitrev }
+    }
+
+    fn nstages(&self) -> usize {
+        self.bitrev.len().trailing_zeros() as usize
+    }
+
+    fn process(&self, buf: &mut [Complex<i64>], cfg: &FixedPointCfg, counters: &mut [u64]) {
+        let n = self.bitrev.len();
+        assert_eq!(buf.len(), n);
+        (0..n).for_each(|i| {
+            let j = self.bitrev[i];
+            if i < j {
+                buf.swap(i, j);
+            }
+        });
+
+        let r = cfg.rounding;
+        let fit = |x: i64, counter: &mut u64| fit_width(x, cfg.data_bits, cfg.overflow, counter);
+        let mut half = 1;
+        for (stage, counter) in counters.iter_mut().enumerate() {
+            let shift = (cfg.fft_shift >> stage) & 1;
+            let step = n / (half * 2);
+            for start in (0..n).step_by(half * 2) {
+                for k in 0..half {
+                    let a = buf[start + k];
+                    let b = buf[start + k + half];
+                    let w = self.twiddle[k * step];
+                    let t = Complex::new(
+                        shift_round(b.re * w.re - b.im * w.im, cfg.twiddle_bits - 1, r),
+                        shift_round(b.re * w.im + b.im * w.re, cfg.twiddle_bits - 1, r),
+                    );
+                    let (p, q) = (a + t, a - t);
+                    buf[start + k] = Complex::new(
+                        fit(shift_round(p.re, shift, r), counter),
+                        fit(shift_round(p.im, shift, r), counter),
+                    );
+                    buf[start + k + half] = Complex::new(
+                        fit(shift_round(q.re, shift, r), counter),
+                        fit(shift_round(q.im, shift, r), counter),
+                    );
+                }
+            }
+            half *= 2;
+        }
+    }
+}
+
+/// requantize the accumulated filter outputs to the data width, after shifting right by `cfg.fir_shift+extra_shift` bits
+fn requantize(
+    x: Complex<i64>,
+    extra_shift: u32,
+    cfg: &FixedPointCfg,
+    counter: &mut u64,
+) -> Complex<i64> {
+    let f = |x: i64, counter: &mut u64| {
+        fit_width(
+            shift_round(x, cfg.fir_shift + extra_shift, cfg.rounding),
+            cfg.data_bits,
+            cfg.overflow,
+            counter,
+        )
+    };
+    Complex::new(f(x.re, counter), f(x.im, counter))
+}
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for i8 {}
+    impl Sealed for i16 {}
+}
+
+/// Input samples of the fixed-point filter banks, i.e., `i8` or `i16` from an ADC
+pub trait FixedPointInput: Copy + Into<i64> + std::fmt::Debug + private::Sealed {
+    /// width of the samples
+    const BITS: u32;
+}
+
+impl FixedPointInput for i8 {
+    const BITS: u32 = 8;
+}
+
+impl FixedPointInput for i16 {
+    const BITS: u32 = 16;
+}
+
+/// Fixed-point version of [`crate::cspfb::Analyzer`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CspfbAnalyzer<I>
+where
+    I: std::fmt::Debug,
+{
+    batch_filter: BatchFilter<i64, i64>,
+    buffer: Vec<I>,
+    /// number of frames that have been produced, see [`Self::timing`]
+    #[serde(default)]
+    nframes: usize,
+    fft: FixedFft,
+    cfg: FixedPointCfg,
+    /// scale factor of the quantized coefficients
+    coeff_scale: f64,
+    overflow: OverflowCounters,
+}
+
+impl<I> CspfbAnalyzer<I>
+where
+    I: FixedPointInput,
+{
+    /// constructor
+    /// * `nch` - number of channels including both pos and neg ones, which should be a power of 2
+    /// * `coeff` - prototype low-pass filter, e.g., from [`crate::windowed_fir::pfb_coeff`], which is quantized according to `cfg`
+    /// * `cfg` - bit widths and arithmetic modes
+    /// ```
+    /// extern crate rsdsp;
+    /// use rsdsp::{
+    ///     windowed_fir
+    ///     , fixed_point::{CspfbAnalyzer, FixedPointCfg, Rounding, OverflowMode}
+    /// };
+    ///
+    /// let nch=32;
+    /// let coeff=windowed_fir::pfb_coeff::<f64>(nch, 8, 1.1);
+    /// let cfg=FixedPointCfg{coeff_bits: 18, data_bits: 18, twiddle_bits: 18, fir_shift: 17,
+    ///     fft_shift: 0b11111, rounding: Rounding::RoundHalfEven, overflow: OverflowMode::Saturate};
+    /// let mut pfb=CspfbAnalyzer::<i8>::new(nch, coeff.as_slice().unwrap(), cfg);
+    /// let input_signal=vec![1_i8; 256];
+    /// let channelized_signal=pfb.analyze(&input_signal);
+    /// assert_eq!(channelized_signal.shape(), &[nch, 256/nch]);
+    /// assert_eq!(pfb.overflow().total(), 0);
+    /// ```
+    pub fn new(nch: usize, coeff: &[f64], cfg: FixedPointCfg) -> Self {
+        let tap = coeff.len() / nch;
+        assert!(nch * tap == coeff.len());
+        cfg.check_widths();
+        cfg.check_accumulator(I::BITS, tap);
+        let (coeff, coeff_scale) = quantize_coeff(coeff, cfg.coeff_bits);
+        let batch_filter = BatchFilter::new(polyphase_decomp(&coeff, nch).view());
+        let fft = FixedFft::new(nch, cfg.twiddle_bits);
+        let overflow = OverflowCounters::new(fft.nstages());
+        Self {
+            batch_filter,
+            buffer: Vec::new(),
+            nframes: 0,
+            fft,
+            cfg,
+            coeff_scale,
+            overflow,
+        }
+    }
+
+    /// return the number of channels
+    pub fn nch(&self) -> usize {
+        self.batch_filter.nch()
+    }
+
+    pub fn predict_output_length(&self, input_len: usize) -> usize {
+        (self.buffer.len() + input_len) / self.nch()
+    }
+
+    /// timing of the output frames, the same as that of [`crate::cspfb::Analyzer`]
+    pub fn timing(&self) -> Timing {
+        Timing::frames(
+            self.nframes,
+            self.nch(),
+            self.nch() * self.batch_filter.tap(),
+        )
+    }
+
+    /// group delay, the same as that of [`crate::cspfb::Analyzer`]
+    pub fn group_delay(&self) -> GroupDelay {
+        GroupDelay::fir(self.nch() * self.batch_filter.tap(), self.nch() as f64)
+    }
+
+    /// ratio of the output to that of a floating point [`crate::cspfb::Analyzer`] with the same prototype filter
+    pub fn output_scale(&self) -> f64 {
+        output_scale(&self.cfg, self.coeff_scale, self.fft.nstages())
+    }
+
+    /// overflow counters
+    pub fn overflow(&self) -> &OverflowCounters {
+        &self.overflow
+    }
+
+    pub fn reset_overflow(&mut self) {
+        self.overflow.reset();
+    }
+
+    /// filter the buffered input and the new input, the remaining samples are buffered
+    fn filter_input(&mut self, input_signal: &[I]) -> Array2<i64> {
+        let nch = self.nch();
+        let batch = self.predict_output_length(input_signal.len());
+        self.buffer.extend_from_slice(input_signal);
+        let signal = self.buffer[..batch * nch]
+            .iter()
+            .map(|&x| x.into())
+            .collect::<Vec<i64>>();
+        let signal = ArrayView2::from_shape((batch, nch), &signal).unwrap();
+        let mut filtered = Array2::<i64>::zeros((batch, nch));
+        self.batch_filter.filter_block(signal, filtered.view_mut());
+        self.buffer.drain(..batch * nch);
+        self.nframes += batch;
+        filtered
+    }
+
+    /// Channelize input signal
+    /// * `input_signal` - a 1-d slice containing time domain input signal
+    /// * return value - channelized data, with `nch` rows
+    pub fn analyze(&mut self, input_signal: &[I]) -> Array2<Complex<i64>> {
+        let filtered = self.filter_input(input_signal);
+        let mut result = Array2::<Complex<i64>>::zeros((self.nch(), filtered.nrows()));
+        let mut buf = vec![Complex::<i64>::new(0, 0); self.nch()];
+        filtered
+            .outer_iter()
+            .zip(result.axis_iter_mut(Axis(1)))
+            .for_each(|(x, mut y)| {
+                buf.iter_mut().zip(x.iter()).for_each(|(a, &b)| {
+                    *a = requantize(Complex::new(b, 0), 0, &self.cfg, &mut self.overflow.fir)
+                });
+                self.fft
+                    .process(&mut buf, &self.cfg, &mut self.overflow.fft);
+                y.iter_mut().zip(&buf).for_each(|(a, &b)| *a = b);
+            });
+        result
+    }
+}
+
+impl<I> Channelizer for CspfbAnalyzer<I>
+where
+    I: FixedPointInput,
+{
+    type Input = [I];
+    type Output = Complex<i64>;
+
+    fn nch(&self) -> usize {
+        CspfbAnalyzer::nch(self)
+    }
+
+    fn output_layout(&self) -> OutputLayout {
+        OutputLayout::FftOrder
+    }
+
+    fn predict_output_length(&self, input_len: usize) -> usize {
+        CspfbAnalyzer::predict_output_length(self, input_len)
+    }
+
+    fn timing(&self) -> Timing {
+        CspfbAnalyzer::timing(self)
+    }
+
+    fn group_delay(&self) -> GroupDelay {
+        CspfbAnalyzer::group_delay(self)
+    }
+
+    fn analyze(&mut self, input: &[I]) -> Array2<Complex<i64>> {
+        CspfbAnalyzer::analyze(self, input)
+    }
+
+    /// the data path is sequential, so that it is the same as [`Self::analyze`]
+    fn analyze_par(&mut self, input: &[I]) -> Array2<Complex<i64>> {
+        CspfbAnalyzer::analyze(self, input)
+    }
+}
+
+/// Fixed-point version of [`crate::ospfb::Analyzer`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OspfbAnalyzer<I>
+where
+    I: std::fmt::Debug,
+{
+    /// filters for even channels
+    filter_even: BatchFilter<i64, i64>,
+    /// filters for odd channels
+    filter_odd: BatchFilter<Complex<i64>, i64>,
+    buffer: Vec<I>,
+    /// number of frames that have been produced, see [`Self::timing`]
+    #[serde(default)]
+    nframes: usize,
+    /// quantized factors shifting input signal by half of the channel spacing
+    shifter: Vec<Complex<i64>>,
+    /// index of the next factor of `shifter`
+    shifter_idx: usize,
+    fft: FixedFft,
+    cfg: FixedPointCfg,
+    /// scale factor of the quantized coefficients
+    coeff_scale: f64,
+    /// overflows of the even and odd channels are counted together
+    overflow: OverflowCounters,
+}
+
+impl<I> OspfbAnalyzer<I>
+where
+    I: FixedPointInput,
+{
+    /// constructor
+    /// * `nch_total` - total number of channels, including even and odd, pos and neg channels, which should be a power of 2
+    /// * `coeff` - prototype low-pass filter, the length of which should be equal to `nch_total`/2*`tap_per_ch`
+    /// * `cfg` - bit widths and arithmetic modes
+    /// ```
+    /// extern crate rsdsp;
+    /// use rsdsp::{
+    ///     windowed_fir
+    ///     , fixed_point::{OspfbAnalyzer, FixedPointCfg, Rounding, OverflowMode}
+    /// };
+    ///
+    /// let nch=32;
+    /// let coeff=windowed_fir::pfb_coeff::<f64>(nch/2, 8, 1.1);
+    /// let cfg=FixedPointCfg{coeff_bits: 18, data_bits: 18, twiddle_bits: 18, fir_shift: 17,
+    ///     fft_shift: 0b1111, rounding: Rounding::RoundHalfEven, overflow: OverflowMode::Saturate};
+    /// let mut pfb=OspfbAnalyzer::<i8>::new(nch, coeff.as_slice().unwrap(), cfg);
+    /// let input_signal=vec![1_i8; 256];
+    /// let channelized_signal=pfb.analyze(&input_signal);
+    /// assert_eq!(channelized_signal.shape(), &[nch, 256/(nch/2)]);
+    /// assert_eq!(pfb.overflow().total(), 0);
+    /// ```
+    pub fn new(nch_total: usize, coeff: &[f64], cfg: FixedPointCfg) -> Self {
+        let nch_each = nch_total / 2;
+        let tap = coeff.len() / nch_each;
+        assert!(nch_each * tap == coeff.len());
+        cfg.check_widths();
+        // the input of the odd channels is multiplied by the shifter in full precision
+        cfg.check_accumulator(I::BITS + cfg.twiddle_bits, tap);
+        let (coeff, coeff_scale) = quantize_coeff(coeff, cfg.coeff_bits);
+        let coeff = polyphase_decomp(&coeff, nch_each);
+        let filter_even = BatchFilter::new(coeff.view());
+        let filter_odd = BatchFilter::new(coeff.view());
+        let shifter = (0..nch_total)
+            .map(|i| quantize_phase(-PI * i as f64 / nch_each as f64, cfg.twiddle_bits))
+            .collect();
+        let fft = FixedFft::new(nch_each, cfg.twiddle_bits);
+        let overflow = OverflowCounters::new(fft.nstages());
+        Self {
+            filter_even,
+            filter_odd,
+            buffer: Vec::new(),
+            nframes: 0,
+            shifter,
+            shifter_idx: 0,
+            fft,
+            cfg,
+            coeff_scale,
+            overflow,
+        }
+    }
+
+    pub fn nch_total(&self) -> usize {
+        self.filter_even.nch() * 2
+    }
+
+    pub fn predict_output_length(&self, input_len: usize) -> usize {
+        (self.buffer.len() + input_len) / self.filter_even.nch()
+    }
+
+    /// timing of the output frames, the same as that of [`crate::ospfb::Analyzer`]
+    pub fn timing(&self) -> Timing {
+        let nch_each = self.filter_even.nch();
+        Timing::frames(self.nframes, nch_each, nch_each * self.filter_even.tap())
+    }
+
+    /// group delay, the same as that of [`crate::ospfb::Analyzer`]
+    pub fn group_delay(&self) -> GroupDelay {
+        let nch_each = self.filter_even.nch();
+        GroupDelay::fir(nch_each * self.filter_even.tap(), nch_each as f64)
+    }
+
+    /// ratio of the output to that of a floating point [`crate::ospfb::Analyzer`] with the same prototype filter
+    pub fn output_scale(&self) -> f64 {
+        output_scale(&self.cfg, self.coeff_scale, self.fft.nstages())
+    }
+
+    /// overflow counters
+    pub fn overflow(&self) -> &OverflowCounters {
+        &self.overflow
+    }
+
+    pub fn reset_overflow(&mut self) {
+        self.overflow.reset();
+    }
+
+    /// Channelize input signal
+    /// * `input_signal` - a 1-d slice containing time domain input signal
+    /// * return value - channelized data, with `nch_total` rows
+    pub fn analyze(&mut self, input_signal: &[I]) -> Array2<Complex<i64>> {
+        let nch_each = self.filter_even.nch();
+        let batch = self.predict_output_length(input_signal.len());
+        self.buffer.extend_from_slice(input_signal);
+
+        let mut result = Array2::<Complex<i64>>::zeros((nch_each * 2, batch));
+        let mut x_even = vec![0_i64; nch_each];
+        let mut x_odd = vec![Complex::<i64>::new(0, 0); nch_each];
+        let mut y_even = vec![0_i64; nch_each];
+        let mut y_odd = vec![Complex::<i64>::new(0, 0); nch_each];
+        let mut buf_even = vec![Complex::<i64>::new(0, 0); nch_each];
+        let mut buf_odd = vec![Complex::<i64>::new(0, 0); nch_each];
+        let twiddle_shift = self.cfg.twiddle_bits - 1;
+
+        for (x, mut y) in self
+            .buffer
+            .chunks_exact(nch_each)
+            .zip(result.axis_iter_mut(Axis(1)))
+        {
+            x.iter()
+                .zip(x_even.iter_mut().zip(x_odd.iter_mut()))
+                .for_each(|(&x1, (e, o))| {
+                    *e = x1.into();
+                    let f = self.shifter[self.shifter_idx];
+                    self.shifter_idx = (self.shifter_idx + 1) % self.shifter.len();
+                    // kept in full precision, the twiddle scaling is removed after the filters
+                    *o = f * *e;
+                });
+            self.filter_even.filter_into(&x_even, &mut y_even);
+            self.filter_odd.filter_into(&x_odd, &mut y_odd);
+            buf_even.iter_mut().zip(&y_even).for_each(|(a, &b)| {
+                *a = requantize(Complex::new(b, 0), 0, &self.cfg, &mut self.overflow.fir)
+            });
+            buf_odd.iter_mut().zip(&y_odd).for_each(|(a, &b)| {
+                *a = requantize(b, twiddle_shift, &self.cfg, &mut self.overflow.fir)
+            });
+            self.fft
+                .process(&mut buf_even, &self.cfg, &mut self.overflow.fft);
+            self.fft
+                .process(&mut buf_odd, &self.cfg, &mut self.overflow.fft);
+            y.iter_mut()
+                .zip(
+                    buf_even
+                        .iter()
+                        .zip(buf_odd.iter())
+                        .flat_map(|(e, o)| [e, o]),
+                )
+                .for_each(|(a, &b)| *a = b);
+        }
+        self.buffer.drain(..batch * nch_each);
+        self.nframes += batch;
+        result
+    }
+}
+
+impl<I> Channelizer for OspfbAnalyzer<I>
+where
+    I: FixedPointInput,
+{
+    type Input = [I];
+    type Output = Complex<i64>;
+
+    fn nch(&self) -> usize {
+        self.nch_total()
+    }
+
+    fn output_layout(&self) -> OutputLayout {
+        OutputLayout::FftOrder
+    }
+
+    fn predict_output_length(&self, input_len: usize) -> usize {
+        OspfbAnalyzer::predict_output_length(self, input_len)
+    }
+
+    fn timing(&self) -> Timing {
+        OspfbAnalyzer::timing(self)
+    }
+
+    fn group_delay(&self) -> GroupDelay {
+        OspfbAnalyzer::group_delay(self)
+    }
+
+    fn analyze(&mut self, input: &[I]) -> Array2<Complex<i64>> {
+        OspfbAnalyzer::analyze(self, input)
+    }
+
+    /// the data path is sequential, so that it is the same as [`Self::analyze`]
+    fn analyze_par(&mut self, input: &[I]) -> Array2<Complex<i64>> {
+        OspfbAnalyzer::analyze(self, input)
+    }
+}
+
+fn output_scale(cfg: &FixedPointCfg, coeff_scale: f64, nstages: usize) -> f64 {
+    let nshift = (0..nstages)
+        .filter(|&i| (cfg.fft_shift >> i) & 1 == 1)
+        .count() as u32;
+    coeff_scale / 2_f64.powi((cfg.fir_shift + nshift) as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cspfb, ospfb, windowed_fir::pfb_coeff};
+
+    fn cfg() -> FixedPointCfg {
+        FixedPointCfg {
+            coeff_bits: 18,
+            data_bits: 24,
+            twiddle_bits: 18,
+            fir_shift: 10,
+            fft_shift: 0b1010,
+            rounding: Rounding::RoundHalfEven,
+            overflow: OverflowMode::Saturate,
+        }
+    }
+
+    fn adc_noise(n: usize, mut seed: u64) -> Vec<i8> {
+        (0..n)
+            .map(|_| {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (seed >> 56) as u8 as i8
+            })
+            .collect()
+    }
+
+    fn rel_err(fixed: &Array2<Complex<i64>>, float: &Array2<Complex<f64>>, scale: f64) -> f64 {
+        let (e2, s2) = fixed
+            .iter()
+            .zip(float.iter())
+            .fold((0.0, 0.0), |(e, s), (a, b)| {
+                let a = Complex::new(a.re as f64, a.im as f64) / scale;
+                (e + (a - b).norm_sqr(), s + b.norm_sqr())
+            });
+        (e2 / s2).sqrt()
+    }
+
+    #[test]
+    fn rounding_test() {
+        assert_eq!(shift_round(5, 1, Rounding::Truncate), 2);
+        assert_eq!(shift_round(-5, 1, Rounding::Truncate), -3);
+        assert_eq!(shift_round(5, 1, Rounding::RoundHalfUp), 3);
+        assert_eq!(shift_round(-5, 1, Rounding::RoundHalfUp), -2);
+        assert_eq!(shift_round(5, 1, Rounding::RoundHalfEven), 2);
+        assert_eq!(shift_round(7, 1, Rounding::RoundHalfEven), 4);
+        assert_eq!(shift_round(-5, 1, Rounding::RoundHalfEven), -2);
+        let mut n = 0;
+        assert_eq!(fit_width(130, 8, OverflowMode::Saturate, &mut n), 127);
+        assert_eq!(fit_width(130, 8, OverflowMode::Wrap, &mut n), -126);
+        assert_eq!(fit_width(-100, 8, OverflowMode::Wrap, &mut n), -100);
+        assert_eq!(n, 2);
+    }
+
+    #[test]
+    fn float_consistency_test() {
+        let nch = 32;
+        let coeff = pfb_coeff::<f64>(nch, 8, 1.1);
+        let coeff = coeff.as_slice().unwrap();
+        let signal = adc_noise(nch * 64, 9);
+        let signal_f = signal.iter().map(|&x| x as f64).collect::<Vec<_>>();
+
+        let mut pfb = CspfbAnalyzer::<i8>::new(nch, coeff, cfg());
+        let mut pfb_f = cspfb::Analyzer::<f64, f64>::new(nch, coeff);
+        let y = signal
+            .chunks(100)
+            .map(|x| pfb.analyze(x))
+            .reduce(|a, b| ndarray::concatenate![Axis(1), a, b])
+            .unwrap();
+        let y_f = pfb_f.analyze(&signal_f);
+        assert_eq!(y.shape(), y_f.shape());
+        assert!(rel_err(&y, &y_f, pfb.output_scale()) < 1e-3);
+        assert_eq!(pfb.overflow().total(), 0);
+
+        let coeff = pfb_coeff::<f64>(nch / 2, 8, 1.1);
+        let coeff = coeff.as_slice().unwrap();
+        let mut pfb = OspfbAnalyzer::<i8>::new(nch, coeff, cfg());
+        let mut pfb_f = ospfb::Analyzer::<f64, f64>::new(nch, coeff);
+        let y = pfb.analyze(&signal);
+        let y_f = pfb_f.analyze(&signal_f);
+        assert_eq!(y.shape(), y_f.shape());
+        assert!(rel_err(&y, &y_f, pfb.output_scale()) < 1e-3);
+        assert_eq!(pfb.overflow().total(), 0);
+    }
+
+    #[test]
+    fn overflow_test() {
+        let nch = 32;
+        let coeff = pfb_coeff::<f64>(nch, 8, 1.1);
+        let coeff = coeff.as_slice().unwrap();
+        let signal = vec![i16::MAX; nch * 16];
+        let cfg = FixedPointCfg {
+            data_bits: 18,
+            fir_shift: 17,
+            fft_shift: 0,
+            ..cfg()
+        };
+        let mut pfb = CspfbAnalyzer::<i16>::new(nch, coeff, cfg.clone());
+        let y = pfb.analyze(&signal);
+        assert!(pfb.overflow().total() > 0);
+        assert!(y
+            .iter()
+            .all(|x| x.re.abs() <= (1 << 17) && x.im.abs() <= (1 << 17)));
+        pfb.reset_overflow();
+        assert_eq!(pfb.overflow().total(), 0);
+
+        // shifting at every stage avoids the overflow
+        let mut pfb = CspfbAnalyzer::<i16>::new(
+            nch,
+            coeff,
+            FixedPointCfg {
+                fft_shift: 0b11111,
+                ..cfg
+            },
+        );
+        pfb.analyze(&signal);
+        assert_eq!(pfb.overflow().fft.iter().sum::<u64>(), 0);
+
+        let coeff = pfb_coeff::<f64>(nch / 2, 8, 1.1);
+        let coeff = coeff.as_slice().unwrap();
+        let mut pfb = OspfbAnalyzer::<i16>::new(nch, coeff, cfg.clone());
+        let y = pfb.analyze(&signal);
+        assert!(pfb.overflow().total() > 0);
+        assert!(y
+            .iter()
+            .all(|x| x.re.abs() <= (1 << 17) && x.im.abs() <= (1 << 17)));
+        pfb.reset_overflow();
+        assert_eq!(pfb.overflow().total(), 0);
+
+        let mut pfb = OspfbAnalyzer::<i16>::new(
+            nch,
+            coeff,
+            FixedPointCfg {
+                fft_shift: 0b1111,
+                ..cfg
+            },
+        );
+        pfb.analyze(&signal);
+        assert_eq!(pfb.overflow().fft.iter().sum::<u64>(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "coeff_bits should be in 1..=63")]
+    fn invalid_width_test() {
+        let coeff = pfb_coeff::<f64>(32, 8, 1.1);
+        let cfg = FixedPointCfg {
+            coeff_bits: 0,
+            ..cfg()
+        };
+        CspfbAnalyzer::<i8>::new(32, coeff.as_slice().unwrap(), cfg);
+    }
+
+    #[test]
+    #[should_panic(expected = "the accumulator of the filters needs 79 bits")]
+    fn cspfb_accumulator_test() {
+        // 16-bit input, 60-bit coefficients and 8 taps
+        let coeff = pfb_coeff::<f64>(32, 8, 1.1);
+        let cfg = FixedPointCfg {
+            coeff_bits: 60,
+            ..cfg()
+        };
+        CspfbAnalyzer::<i16>::new(32, coeff.as_slice().unwrap(), cfg);
+    }
+
+    #[test]
+    #[should_panic(expected = "the accumulator of the filters needs 67 bits")]
+    fn ospfb_accumulator_test() {
+        // 16-bit input, 24-bit shifter, 24-bit coefficients and 8 taps
+        let coeff = pfb_coeff::<f64>(16, 8, 1.1);
+        let cfg = FixedPointCfg {
+            coeff_bits: 24,
+            twiddle_bits: 24,
+            ..cfg()
+        };
+        OspfbAnalyzer::<i16>::new(32, coeff.as_slice().unwrap(), cfg);
+    }
+}
@@ -1,14 +1,22 @@
 //! fractional delayer
 
 use ndarray::ScalarOperand;
-use num::traits::{Float, FloatConst, NumAssign, NumCast};
+use num::{
+    complex::Complex,
+    traits::{Float, FloatConst, NumAssign, NumCast, Zero},
+};
 
 use std::{
     iter::Sum,
     ops::{Add, Mul, MulAssign},
 };
 
-use crate::{cfg::DelayerCfg, utils::ConcatedSlice, window_funcs::apply_blackman_window};
+use crate::{
+    cfg::DelayerCfg,
+    timing::{GroupDelay, Timing},
+    utils::ConcatedSlice,
+    window_funcs::apply_blackman_window,
+};
 
 /// Delay value
 #[derive(Clone, Copy)]
@@ -73,18 +81,83 @@ pub fn delayer_coeff_rev<T>(dt: T, half_tap: usize) -> Vec<T>
 where
     T: Float + FloatConst + NumAssign + std::fmt::Debug + std::iter::Sum<T>,
 {
-    //let mut result=vec![T::zero(); half_tap*2+1];
-    let mut result: Vec<_> = (0..2 * half_tap + 1)
+    let mut result = vec![T::zero(); half_tap * 2 + 1];
+    delayer_coeff_rev_into(dt, &mut result);
+    result
+}
+
+/// the same as [`delayer_coeff_rev`], but written into `coeff_rev`, the length of which is `2*half_tap+1`
+fn delayer_coeff_rev_into<T>(dt: T, coeff_rev: &mut [T])
+where
+    T: Float + FloatConst + NumAssign + std::fmt::Debug + std::iter::Sum<T>,
+{
+    let half_tap = (coeff_rev.len() - 1) / 2;
+    coeff_rev.iter_mut().enumerate().for_each(|(i, c)| {
+        let x = T::from(i as isize - half_tap as isize).unwrap() + dt;
+        *c = sinc_pi(x);
+    });
+    apply_blackman_window(coeff_rev);
+    //apply_hamming_window(coeff_rev);
+}
+
+/// filter the buffered and the new input with coefficients that may change for each output sample
+/// * `buffer` - the last input samples, see [`FracDelayer::buffer`]
+/// * `max_delay` - max value of possible delay
+/// * `t_out` - time of the next output sample, which is updated
+/// * `signal` - input signal
+/// * `coeff_rev` - scratch for the reversed coefficients, the length of which is the filter tap
+/// * `coeff_at` - a function filling the reversed coefficients for the output sample at time `t`, and returning the integral delay
+fn delay_impl<T, R, F>(
+    buffer: &mut Vec<R>,
+    max_delay: usize,
+    t_out: &mut usize,
+    signal: &[R],
+    coeff_rev: &mut [T],
+    mut coeff_at: F,
+) -> Vec<R>
+where
+    T: Copy,
+    R: Copy + Mul<T, Output = R> + Sum,
+    F: FnMut(usize, &mut [T]) -> isize,
+{
+    let ntap = coeff_rev.len();
+    let concated = ConcatedSlice::new(buffer, signal);
+    let first_idx = max_delay;
+    let end_idx = concated.len() - ntap - max_delay;
+
+    let result: Vec<_> = (first_idx..end_idx)
         .map(|i| {
-            let x = T::from(i as isize - half_tap as isize).unwrap() + dt;
-            sinc_pi(x)
+            let delay_i = coeff_at(*t_out + i - first_idx, coeff_rev);
+            coeff_rev
+                .iter()
+                .enumerate()
+                .map(|(j, &c)| concated[(i as isize + j as isize - delay_i) as usize] * c)
+                .sum()
         })
         .collect();
-    apply_blackman_window(&mut result);
-    //apply_hamming_window(&mut result);
+    let l = concated.len();
+    let l1 = buffer.len();
+
+    *buffer = (0..l1).map(|i| concated[l - l1 + i]).collect();
+    *t_out += result.len();
     result
 }
 
+/// timing of the output of a delayer, which outputs one sample for each input sample,
+/// and the output `t` without delay is the input `t-max_delay-half_tap-1`, due to the buffered zeros
+fn delayer_timing(t_out: usize, max_delay: usize, half_tap: usize) -> Timing {
+    Timing {
+        next_output: t_out,
+        offset: -((max_delay + half_tap + 1) as f64),
+        step: 1.0,
+    }
+}
+
+/// group delay of a delayer, see [`delayer_timing`]
+fn delayer_group_delay(max_delay: usize, half_tap: usize) -> GroupDelay {
+    GroupDelay::new((max_delay + half_tap + 1) as f64, 1.0)
+}
+
 /// Fractional delayer
 #[derive(Clone, Debug)]
 pub struct FracDelayer<T, R = T>
@@ -94,6 +167,31 @@ where
     pub coeff_rev: Vec<T>,
     pub buffer: Vec<R>,
     pub max_delay: usize,
+    /// number of samples that have been output, i.e., the time of the next output sample
+    pub t_out: usize,
+}
+
+/// Delay model, which is a polynomial of time
+#[derive(Clone, Debug)]
+pub struct DelayPolynomial<T> {
+    /// reference time, in the unit of output samples, see [`FracDelayer::t_out`]
+    pub t0: T,
+    /// polynomial coefficients, i.e., the delay at time `t` is `sum(coeff[k]*(t-t0)^k)`
+    pub coeff: Vec<T>,
+}
+
+impl<T> DelayPolynomial<T>
+where
+    T: Float,
+{
+    /// evaluate the delay at time `t`
+    pub fn eval(&self, t: T) -> T {
+        let dt = t - self.t0;
+        self.coeff
+            .iter()
+            .rev()
+            .fold(T::zero(), |acc, &c| acc * dt + c)
+    }
 }
 
 impl<T, R> FracDelayer<T, R>
@@ -126,11 +224,34 @@ where
             coeff_rev: delayer_coeff_rev(T::zero(), half_tap),
             buffer: vec![R::zero(); 2 * max_delay + half_tap * 2 + 1],
             max_delay,
+            t_out: 0,
         }
     }
 
+    /// timing of the output, i.e., the intrinsic delay, the delay applied by [`Self::delay`] is not included
+    /// ```
+    /// extern crate rsdsp;
+    /// use rsdsp::frac_delayer::FracDelayer;
+    ///
+    /// let mut delayer=FracDelayer::<f64>::new(10, 4);
+    /// let mut x=vec![0.0; 100];
+    /// x[50]=1.0;
+    /// let y=delayer.delay(&x, 2.0);
+    /// let peak=y.iter().enumerate().fold(0, |i, (j, &v)| if v>y[i] {j} else {i});
+    /// // the input sample 50 is delayed by 2 samples
+    /// assert_eq!(delayer.timing().position(peak)-2.0, 50.0);
+    /// ```
+    pub fn timing(&self) -> Timing {
+        delayer_timing(self.t_out, self.max_delay, (self.coeff_rev.len() - 1) / 2)
+    }
+
+    /// group delay, i.e., the intrinsic delay of `max_delay+half_tap+1` samples, see [`Self::timing`]
+    pub fn group_delay(&self) -> GroupDelay {
+        delayer_group_delay(self.max_delay, (self.coeff_rev.len() - 1) / 2)
+    }
+
     /// delay the input signal
-    /// Note that there is an unchangable intrinsic delay related to the filter tap
+    /// Note that there is an unchangable intrinsic delay related to the filter tap, see [`Self::group_delay`]
     /// * `signal` - input signal
     /// * `dv` - delay value
     pub fn delay<U>(&mut self, signal: &[R], dv: U) -> Vec<R>
@@ -164,8 +285,66 @@ where
         let l1 = self.buffer.len();
 
         self.buffer = (0..l1).map(|i| concated[l - l1 + i]).collect();
+        self.t_out += result.len();
         result
     }
+
+    /// delay the input signal by a delay that varies from sample to sample,
+    /// both the integral and the fractional parts are updated for each output sample
+    /// * `signal` - input signal
+    /// * `delay_at` - a function returning the delay of the output sample at time `t`, see [`Self::t_out`]
+    fn delay_each<F>(&mut self, signal: &[R], mut delay_at: F) -> Vec<R>
+    where
+        F: FnMut(usize) -> T,
+    {
+        let max_delay = self.max_delay;
+        // the fractional delay of the current `coeff_rev`
+        let mut last_f = None;
+        delay_impl(
+            &mut self.buffer,
+            max_delay,
+            &mut self.t_out,
+            signal,
+            &mut self.coeff_rev,
+            |t, coeff_rev| {
+                let DelayValue { i, f } = delay_at(t).to_delay_value();
+                assert!(i.unsigned_abs() < max_delay);
+                if last_f != Some(f) {
+                    delayer_coeff_rev_into(f, coeff_rev);
+                    last_f = Some(f);
+                }
+                i
+            },
+        )
+    }
+
+    /// delay the input signal by a different delay for each sample
+    /// * `signal` - input signal
+    /// * `delays` - delay of each output sample, the length of which should be the same as `signal`
+    pub fn delay_varying(&mut self, signal: &[R], delays: &[T]) -> Vec<R> {
+        assert_eq!(signal.len(), delays.len());
+        let t0 = self.t_out;
+        self.delay_each(signal, |t| delays[t - t0])
+    }
+
+    /// delay the input signal by a delay model, which is evaluated for each output sample,
+    /// so that the delay is continuous across successive calls
+    /// * `signal` - input signal
+    /// * `model` - delay model, with the time in the unit of output samples, see [`Self::t_out`]
+    /// ```
+    /// extern crate rsdsp;
+    /// use rsdsp::frac_delayer::{FracDelayer, DelayPolynomial};
+    ///
+    /// let mut delayer=FracDelayer::<f64>::new(10, 4);
+    /// // delay increases by 0.001 sample per sample
+    /// let model=DelayPolynomial{t0: 0.0, coeff: vec![0.5, 0.001]};
+    /// let y1=delayer.delay_poly(&vec![1.0; 100], &model);
+    /// let y2=delayer.delay_poly(&vec![1.0; 100], &model);
+    /// assert_eq!(y1.len()+y2.len(), delayer.t_out);
+    /// ```
+    pub fn delay_poly(&mut self, signal: &[R], model: &DelayPolynomial<T>) -> Vec<R> {
+        self.delay_each(signal, |t| model.eval(T::from(t).unwrap()))
+    }
 }
 
 /// construct a delayer from [`crate::cfg::DelayerCfg`]
@@ -196,9 +375,289 @@ where
     FracDelayer::<T, R>::new(cfg.max_delay, cfg.half_tap)
 }
 
+/// Approximations of [`delayer_coeff_rev`], which are cheaper to evaluate
+pub trait DelayerCoeff<T>
+where
+    T: Float + FloatConst + NumAssign + std::fmt::Debug + Sum,
+{
+    /// `2*half_tap+1` is the filter tap
+    fn half_tap(&self) -> usize;
+
+    /// write the reversed coefficients for the fractional delay `f`, which is in `[-1, 1]`
+    fn coeff_rev_into(&self, f: T, coeff_rev: &mut [T]);
+
+    /// the worst-case phase error, in radians, of the frequency response compared with that of [`delayer_coeff_rev`],
+    /// evaluated on a grid of fractional delays in `[-1, 1]` and frequencies in `[0, omega_max]`
+    /// * `omega_max` - max angular frequency, in radians per sample, which should be within the pass band
+    fn max_phase_error(&self, omega_max: T) -> T {
+        let half_tap = self.half_tap();
+        let nf = 1000;
+        let nomega = 64;
+        let mut coeff_rev = vec![T::zero(); 2 * half_tap + 1];
+        (0..=nf)
+            .map(|k| {
+                let f = T::from(2 * k).unwrap() / T::from(nf).unwrap() - T::one();
+                let exact = delayer_coeff_rev(f, half_tap);
+                self.coeff_rev_into(f, &mut coeff_rev);
+                (0..=nomega)
+                    .map(|m| {
+                        let omega = omega_max * T::from(m).unwrap() / T::from(nomega).unwrap();
+                        let resp = |c: &[T]| {
+                            c.iter()
+                                .enumerate()
+                                .map(|(j, &c)| Complex::from_polar(c, omega * T::from(j).unwrap()))
+                                .sum::<Complex<T>>()
+                        };
+                        (resp(&coeff_rev) * resp(&exact).conj()).arg().abs()
+                    })
+                    .fold(T::zero(), T::max)
+            })
+            .fold(T::zero(), T::max)
+    }
+}
+
+/// Reversed coefficients precomputed for `2*nphase+1` fractional delays evenly spaced in `[-1, 1]`,
+/// the coefficients for other fractional delays are linearly interpolated
+#[derive(Clone, Debug)]
+pub struct CoeffTable<T> {
+    /// number of table rows per unit fractional delay
+    pub nphase: usize,
+    /// reversed coefficients for fractional delays `-1+k/nphase`
+    pub table: Vec<Vec<T>>,
+}
+
+impl<T> CoeffTable<T>
+where
+    T: Float + FloatConst + NumAssign + std::fmt::Debug + Sum,
+{
+    /// constructor
+    /// * `half_tap` - `2*half_tap+1` is the filter tap
+    /// * `nphase` - number of table rows per unit fractional delay
+    pub fn new(half_tap: usize, nphase: usize) -> Self {
+        assert!(nphase > 0);
+        let table = (0..=2 * nphase)
+            .map(|k| {
+                let f = T::from(k).unwrap() / T::from(nphase).unwrap() - T::one();
+                delayer_coeff_rev(f, half_tap)
+            })
+            .collect();
+        Self { nphase, table }
+    }
+}
+
+impl<T> DelayerCoeff<T> for CoeffTable<T>
+where
+    T: Float + FloatConst + NumAssign + std::fmt::Debug + Sum,
+{
+    fn half_tap(&self) -> usize {
+        (self.table[0].len() - 1) / 2
+    }
+
+    fn coeff_rev_into(&self, f: T, coeff_rev: &mut [T]) {
+        let pos = (f + T::one()) * T::from(self.nphase).unwrap();
+        let k = <usize as NumCast>::from(pos.floor().max(T::zero()))
+            .unwrap()
+            .min(2 * self.nphase - 1);
+        let a = pos - T::from(k).unwrap();
+        coeff_rev
+            .iter_mut()
+            .zip(self.table[k].iter().zip(&self.table[k + 1]))
+            .for_each(|(c, (&c0, &c1))| *c = c0 + (c1 - c0) * a);
+    }
+}
+
+/// Farrow structure, i.e., each reversed coefficient is a polynomial of the fractional delay,
+/// which interpolates [`delayer_coeff_rev`] at the Chebyshev nodes in `[-1, 1]`
+#[derive(Clone, Debug)]
+pub struct FarrowCoeff<T> {
+    /// `poly[p][j]` is the coefficient of `f^p` of the `j`-th reversed coefficient
+    pub poly: Vec<Vec<T>>,
+}
+
+impl<T> FarrowCoeff<T>
+where
+    T: Float + FloatConst + NumAssign + std::fmt::Debug + Sum,
+{
+    /// constructor
+    /// * `half_tap` - `2*half_tap+1` is the filter tap
+    /// * `order` - order of the polynomials
+    pub fn new(half_tap: usize, order: usize) -> Self {
+        let n = order + 1;
+        let ntap = 2 * half_tap + 1;
+        let angle = |k: usize, m: usize| {
+            T::PI() * T::from(k).unwrap() * (T::from(m).unwrap() + T::from(0.5).unwrap())
+                / T::from(n).unwrap()
+        };
+        let samples: Vec<_> = (0..n)
+            .map(|m| delayer_coeff_rev(angle(1, m).cos(), half_tap))
+            .collect();
+
+        // chebyshev expansion, then converted to the power basis
+        let mut poly = vec![vec![T::zero(); ntap]; n];
+        let mut t_prev = vec![T::zero(); n];
+        let mut t_cur = vec![T::zero(); n];
+        t_cur[0] = T::one();
+        (0..n).for_each(|k| {
+            let scale = if k == 0 {
+                T::one()
+            } else {
+                T::from(2).unwrap()
+            } / T::from(n).unwrap();
+            (0..ntap).for_each(|j| {
+                let a = (0..n).map(|m| samples[m][j] * angle(k, m).cos()).sum::<T>() * scale;
+                t_cur
+                    .iter()
+                    .zip(poly.iter_mut())
+                    .for_each(|(&t, p)| p[j] += a * t);
+            });
+            // T_{k+1}(x) = 2*x*T_k(x) - T_{k-1}(x), with T_1(x) = x
+            let mut t_next: Vec<_> = t_prev.iter().map(|&t| -t).collect();
+            (0..n - 1).for_each(|p| {
+                t_next[p + 1] += if k == 0 {
+                    T::one()
+                } else {
+                    T::from(2).unwrap()
+                } * t_cur[p];
+            });
+            t_prev = std::mem::replace(&mut t_cur, t_next);
+        });
+        Self { poly }
+    }
+}
+
+impl<T> DelayerCoeff<T> for FarrowCoeff<T>
+where
+    T: Float + FloatConst + NumAssign + std::fmt::Debug + Sum,
+{
+    fn half_tap(&self) -> usize {
+        (self.poly[0].len() - 1) / 2
+    }
+
+    fn coeff_rev_into(&self, f: T, coeff_rev: &mut [T]) {
+        coeff_rev.fill(T::zero());
+        self.poly.iter().rev().for_each(|p| {
+            coeff_rev
+                .iter_mut()
+                .zip(p)
+                .for_each(|(c, &a)| *c = *c * f + a);
+        });
+    }
+}
+
+/// Fractional delayer with the coefficients generated by a [`DelayerCoeff`], e.g., [`TableDelayer`] and [`FarrowDelayer`],
+/// which has the same intrinsic delay as [`FracDelayer`]
+#[derive(Clone, Debug)]
+pub struct ApproxDelayer<C, T, R = T>
+where
+    T: std::fmt::Debug,
+{
+    pub coeff: C,
+    pub buffer: Vec<R>,
+    pub max_delay: usize,
+    /// number of samples that have been output, i.e., the time of the next output sample
+    pub t_out: usize,
+    /// scratch for the reversed coefficients
+    coeff_rev: Vec<T>,
+}
+
+/// Fractional delayer using [`CoeffTable`]
+pub type TableDelayer<T, R = T> = ApproxDelayer<CoeffTable<T>, T, R>;
+
+/// Fractional delayer using [`FarrowCoeff`]
+pub type FarrowDelayer<T, R = T> = ApproxDelayer<FarrowCoeff<T>, T, R>;
+
+impl<C, T, R> ApproxDelayer<C, T, R>
+where
+    C: DelayerCoeff<T>,
+    T: Copy + Float + FloatConst + NumAssign + Sum + std::fmt::Debug,
+    R: Copy + Mul<T, Output = R> + Sum + Zero,
+{
+    /// construct a delayer
+    /// * `max_delay` - max value of possible delay
+    /// * `coeff` - generator of the coefficients
+    /// ```
+    /// extern crate rsdsp;
+    /// use rsdsp::frac_delayer::{CoeffTable, DelayerCoeff, FracDelayer, TableDelayer};
+    ///
+    /// let mut delayer1=FracDelayer::<f64>::new(10, 8);
+    /// let mut delayer2=TableDelayer::<f64>::new(10, CoeffTable::new(8, 256));
+    /// assert!(delayer2.coeff.max_phase_error(2.0)<1e-4);
+    /// let x:Vec<_>=(0..200).map(|i| (i as f64*0.1).sin()).collect();
+    /// let y1=delayer1.delay(&x, 2.3);
+    /// let y2=delayer2.delay(&x, 2.3);
+    /// assert!(y1.iter().zip(&y2).all(|(a, b)| (a-b).abs()<1e-4));
+    /// ```
+    pub fn new(max_delay: usize, coeff: C) -> Self {
+        let half_tap = coeff.half_tap();
+        Self {
+            coeff,
+            buffer: vec![R::zero(); 2 * max_delay + half_tap * 2 + 1],
+            max_delay,
+            t_out: 0,
+            coeff_rev: vec![T::zero(); 2 * half_tap + 1],
+        }
+    }
+
+    /// timing of the output, see [`FracDelayer::timing`]
+    pub fn timing(&self) -> Timing {
+        delayer_timing(self.t_out, self.max_delay, self.coeff.half_tap())
+    }
+
+    /// group delay, see [`FracDelayer::group_delay`]
+    pub fn group_delay(&self) -> GroupDelay {
+        delayer_group_delay(self.max_delay, self.coeff.half_tap())
+    }
+
+    /// delay the input signal by a delay that may vary from sample to sample
+    fn delay_each<F>(&mut self, signal: &[R], mut delay_at: F) -> Vec<R>
+    where
+        F: FnMut(usize) -> DelayValue<T>,
+    {
+        let max_delay = self.max_delay;
+        let coeff = &self.coeff;
+        delay_impl(
+            &mut self.buffer,
+            max_delay,
+            &mut self.t_out,
+            signal,
+            &mut self.coeff_rev,
+            |t, coeff_rev| {
+                let DelayValue { i, f } = delay_at(t);
+                assert!(i.unsigned_abs() < max_delay);
+                coeff.coeff_rev_into(f, coeff_rev);
+                i
+            },
+        )
+    }
+
+    /// delay the input signal, see [`FracDelayer::delay`]
+    /// * `signal` - input signal
+    /// * `dv` - delay value
+    pub fn delay<U>(&mut self, signal: &[R], dv: U) -> Vec<R>
+    where
+        U: ToDelayValue<T>,
+    {
+        let dv = dv.to_delay_value();
+        self.delay_each(signal, |_| dv)
+    }
+
+    /// delay the input signal by a different delay for each sample, see [`FracDelayer::delay_varying`]
+    pub fn delay_varying(&mut self, signal: &[R], delays: &[T]) -> Vec<R> {
+        assert_eq!(signal.len(), delays.len());
+        let t0 = self.t_out;
+        self.delay_each(signal, |t| delays[t - t0].to_delay_value())
+    }
+
+    /// delay the input signal by a delay model, see [`FracDelayer::delay_poly`]
+    pub fn delay_poly(&mut self, signal: &[R], model: &DelayPolynomial<T>) -> Vec<R> {
+        self.delay_each(signal, |t| model.eval(T::from(t).unwrap()).to_delay_value())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::timing::tests::peak;
     use num::complex::Complex;
 
     use rayon::prelude::*;
@@ -206,7 +665,7 @@ mod tests {
     fn validate_frac_delayer(dt: f64, signal_omega: f64, signal_len: usize) -> (f64, f64) {
         let mut delayer1 = FracDelayer::<f64, Complex<f64>>::new(500, 100);
         let mut delayer2 = FracDelayer::<f64, Complex<f64>>::new(500, 100);
-        let dt_idx = (dt.ceil() as isize).abs() as usize;
+        let dt_idx = (dt.ceil() as isize).unsigned_abs();
         let signal: Vec<_> = (0..signal_len)
             .map(|i| ((i as f64 * signal_omega) * Complex::new(0.0, 1.0)).exp())
             .collect();
@@ -237,4 +696,146 @@ mod tests {
             assert!((a - b).abs() < 0.0025);
         });
     }
+
+    /// max phase error, in degrees, of a tone delayed by a time varying delay, which is processed in chunks
+    fn validate_varying_delayer(model: &DelayPolynomial<f64>, signal_omega: f64) -> f64 {
+        let max_delay = 50;
+        let half_tap = 100;
+        let mut delayer = FracDelayer::<f64, Complex<f64>>::new(max_delay, half_tap);
+        let intrinsic_delay = (max_delay + half_tap + 1) as f64;
+        let signal_len = 8192;
+        let signal: Vec<_> = (0..signal_len)
+            .map(|i| ((i as f64 * signal_omega) * Complex::new(0.0, 1.0)).exp())
+            .collect();
+        let delayed_signal: Vec<_> = signal
+            .chunks(1000)
+            .flat_map(|x| delayer.delay_poly(x, model))
+            .collect();
+        assert_eq!(delayed_signal.len(), signal_len);
+        // skip the output affected by the initial zero buffer
+        let skip = 2 * (max_delay + half_tap + 1);
+        delayed_signal
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .map(|(t, y)| {
+                let t_in = t as f64 - intrinsic_delay - model.eval(t as f64);
+                let answer = Complex::new(0.0, t_in * signal_omega).exp();
+                (y * answer.conj()).arg().to_degrees().abs()
+            })
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn frac_delayer_varying_test() {
+        // constant delay is the same as that of `delay`
+        let signal: Vec<_> = (0..2000)
+            .map(|i| ((i as f64 * 0.1) * Complex::new(0.0, 1.0)).exp())
+            .collect();
+        let mut delayer1 = FracDelayer::<f64, Complex<f64>>::new(20, 10);
+        let mut delayer2 = delayer1.clone();
+        let y1 = delayer1.delay(&signal, -3.3);
+        let y2 = delayer2.delay_varying(&signal, &vec![-3.3; signal.len()]);
+        assert!(y1.iter().zip(&y2).all(|(a, b)| (a - b).norm() < 1e-12));
+        assert_eq!(delayer1.t_out, delayer2.t_out);
+
+        // the delay model is continuous across chunks, and gives the same result as a per-sample delay slice
+        let model = DelayPolynomial {
+            t0: 1000.0,
+            coeff: vec![0.3, 1e-3, 1e-7],
+        };
+        let mut delayer1 = FracDelayer::<f64, Complex<f64>>::new(20, 10);
+        let mut delayer2 = delayer1.clone();
+        let y1: Vec<_> = signal
+            .chunks(333)
+            .flat_map(|x| delayer1.delay_poly(x, &model))
+            .collect();
+        let delays: Vec<_> = (0..signal.len()).map(|t| model.eval(t as f64)).collect();
+        let y2 = delayer2.delay_varying(&signal, &delays);
+        assert!(y1.iter().zip(&y2).all(|(a, b)| (a - b).norm() < 1e-12));
+
+        // accuracy, with the delay crossing integers and changing sign
+        [
+            vec![-2.0, 5e-4],
+            vec![1.5, -4e-4, 1e-8],
+            vec![0.0, 0.0, 0.0, 1e-11],
+        ]
+        .into_par_iter()
+        .for_each(|coeff| {
+            let model = DelayPolynomial { t0: 4096.0, coeff };
+            let err = validate_varying_delayer(&model, f64::PI() / 64.0);
+            assert!(err < 0.0025, "{err}");
+        });
+    }
+
+    #[test]
+    fn approx_delayer_test() {
+        let half_tap = 16;
+        let omega_max = f64::PI() * 0.8;
+        let err_table: Vec<_> = [16, 64, 256]
+            .iter()
+            .map(|&nphase| CoeffTable::<f64>::new(half_tap, nphase).max_phase_error(omega_max))
+            .collect();
+        let err_farrow: Vec<_> = [4, 8, 12]
+            .iter()
+            .map(|&order| FarrowCoeff::<f64>::new(half_tap, order).max_phase_error(omega_max))
+            .collect();
+        assert!(err_table.windows(2).all(|w| w[1] < w[0]));
+        assert!(err_farrow.windows(2).all(|w| w[1] < w[0]));
+        assert!(err_table[2] < 1e-4);
+        assert!(err_farrow[2] < 1e-6);
+
+        // exact at the table rows and the interpolation nodes
+        let table = CoeffTable::<f64>::new(half_tap, 8);
+        let mut coeff_rev = vec![0.0; 2 * half_tap + 1];
+        table.coeff_rev_into(0.25, &mut coeff_rev);
+        assert!(coeff_rev
+            .iter()
+            .zip(delayer_coeff_rev(0.25, half_tap))
+            .all(|(a, b)| (a - b).abs() < 1e-12));
+
+        let signal: Vec<_> = (0..3000)
+            .map(|i| ((i as f64 * 0.3) * Complex::new(0.0, 1.0)).exp())
+            .collect();
+        let model = DelayPolynomial {
+            t0: 1500.0,
+            coeff: vec![0.1, 2e-3],
+        };
+        let mut delayer = FracDelayer::<f64, Complex<f64>>::new(10, half_tap);
+        let mut delayer_table =
+            TableDelayer::<f64, Complex<f64>>::new(10, CoeffTable::new(half_tap, 256));
+        let mut delayer_farrow =
+            FarrowDelayer::<f64, Complex<f64>>::new(10, FarrowCoeff::new(half_tap, 12));
+        let y = delayer.delay_poly(&signal, &model);
+        let y_table: Vec<_> = signal
+            .chunks(700)
+            .flat_map(|x| delayer_table.delay_poly(x, &model))
+            .collect();
+        let y_farrow: Vec<_> = signal
+            .chunks(700)
+            .flat_map(|x| delayer_farrow.delay_poly(x, &model))
+            .collect();
+        assert!(y.iter().zip(&y_table).all(|(a, b)| (a - b).norm() < 1e-4));
+        assert!(y.iter().zip(&y_farrow).all(|(a, b)| (a - b).norm() < 1e-6));
+    }
+
+    #[test]
+    fn group_delay_test() {
+        let n0 = 40;
+        let mut x = vec![0.0; 200];
+        x[n0] = 1.0;
+        let mut delayer = FracDelayer::<f64>::new(10, 8);
+        let mut delayer_farrow = FarrowDelayer::<f64>::new(10, FarrowCoeff::new(8, 8));
+        assert_eq!(delayer.group_delay(), delayer_farrow.group_delay());
+        let gd = delayer.group_delay();
+        assert_eq!(gd.input, gd.output);
+        // the impulse is output after the intrinsic delay, and the delay applied
+        let y: Vec<_> = x.chunks(30).flat_map(|x1| delayer.delay(x1, 3.0)).collect();
+        assert_eq!(peak(&y) as f64, n0 as f64 + gd.input + 3.0);
+        let y: Vec<_> = x
+            .chunks(30)
+            .flat_map(|x1| delayer_farrow.delay(x1, -2.0))
+            .collect();
+        assert_eq!(peak(&y) as f64, n0 as f64 + gd.input - 2.0);
+    }
 }
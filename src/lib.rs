@@ -1,14 +1,25 @@
 pub mod ampl_resp;
 pub mod batch_filter;
+pub mod beamformer;
+pub mod cascade;
 pub mod cfg;
+pub mod channelizer;
+pub mod correlator;
 pub mod csp_pfb;
 pub mod cspfb;
+pub mod delay_comp;
 pub mod down_sample;
 pub mod filter;
+pub mod fixed_point;
 pub mod frac_delayer;
+pub mod multi_stream;
 pub mod oscillator;
 pub mod ospfb;
 pub mod ospfb2;
+pub mod rational_ospfb;
+pub mod rfi;
+pub mod spectrometer;
+pub mod timing;
 pub mod up_sample;
 pub mod utils;
 pub mod window_funcs;
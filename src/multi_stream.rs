@@ -0,0 +1,381 @@
+//! Critical sampling poly phase filter bank for many streams, e.g., antennas and polarizations,
+//! sharing the same prototype filter
+
+use crate::{
+    batch_filter::filter_block_impl,
+    timing::{GroupDelay, Timing},
+    utils::{polyphase_decomp, FftPlan},
+};
+use ndarray::{
+    parallel::prelude::*, s, Array2, Array3, ArrayView1, ArrayView2, ArrayViewMut2, ArrayViewMut3,
+    Axis, ScalarOperand,
+};
+use num::{
+    complex::Complex,
+    traits::{Float, FloatConst, NumAssign},
+};
+
+use serde::{Deserialize, Serialize};
+
+use rustfft::FftNum;
+use std::{iter::Sum, ops::Mul};
+
+/// serialized form of [`Analyzer`], which is validated when deserialized
+#[derive(Deserialize)]
+struct AnalyzerRepr<R, T>
+where
+    T: FftNum,
+{
+    coeff: Vec<T>,
+    nch: usize,
+    state: Vec<Vec<R>>,
+    head: usize,
+    buffer: Vec<Vec<R>>,
+    #[serde(default)]
+    nframes: usize,
+    #[serde(default = "FftPlan::default")]
+    fft: FftPlan<T>,
+}
+
+/// Analyze channelizer for `nstream` streams, which is equivalent to one [`crate::cspfb::Analyzer`] for each stream,
+/// but the polyphase coefficients are stored only once
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "AnalyzerRepr<R, T>")]
+pub struct Analyzer<R, T>
+where
+    R: std::fmt::Debug,
+    T: std::fmt::Debug + FftNum,
+{
+    /// reversed polyphase coefficients, with `tap` rows and `nch` columns, shared by all streams
+    coeff: Vec<T>,
+    /// number of channels
+    nch: usize,
+    /// filter state of each stream, with `tap` rows and `nch` columns, see [`crate::batch_filter::BatchFilter`]
+    state: Vec<Vec<R>>,
+    /// the oldest row of the states
+    head: usize,
+    /// input samples of each stream that are not enough for a frame
+    buffer: Vec<Vec<R>>,
+    /// number of frames that have been produced, see [`Self::timing`]
+    nframes: usize,
+    /// fft plan shared by all the streams, planned again with `nch` if missing from the serialized form
+    fft: FftPlan<T>,
+}
+
+impl<R, T> TryFrom<AnalyzerRepr<R, T>> for Analyzer<R, T>
+where
+    R: std::fmt::Debug,
+    T: std::fmt::Debug + FftNum,
+{
+    type Error = String;
+
+    fn try_from(repr: AnalyzerRepr<R, T>) -> Result<Self, Self::Error> {
+        let nch = repr.nch;
+        if nch == 0 || repr.coeff.is_empty() || !repr.coeff.len().is_multiple_of(nch) {
+            return Err(format!(
+                "coeff has {} elements, which is not a positive multiple of nch {nch}",
+                repr.coeff.len()
+            ));
+        }
+        let tap = repr.coeff.len() / nch;
+        if repr.state.is_empty() {
+            return Err("there should be at least one stream".to_string());
+        }
+        if let Some(len) = repr
+            .state
+            .iter()
+            .map(|x| x.len())
+            .find(|&n| n != repr.coeff.len())
+        {
+            return Err(format!(
+                "a state has {len} elements, while coeff has {}",
+                repr.coeff.len()
+            ));
+        }
+        if repr.head >= tap {
+            return Err(format!("head {} is not less than tap {tap}", repr.head));
+        }
+        if repr.buffer.len() != repr.state.len() {
+            return Err(format!(
+                "there are {} buffers for {} streams",
+                repr.buffer.len(),
+                repr.state.len()
+            ));
+        }
+        let buffer_len = repr.buffer[0].len();
+        if buffer_len >= nch || repr.buffer.iter().any(|x| x.len() != buffer_len) {
+            return Err(format!(
+                "the buffers should be of the same length less than nch {nch}"
+            ));
+        }
+        Ok(Self {
+            coeff: repr.coeff,
+            nch,
+            state: repr.state,
+            head: repr.head,
+            buffer: repr.buffer,
+            nframes: repr.nframes,
+            fft: repr.fft,
+        })
+    }
+}
+
+impl<R, T> Analyzer<R, T>
+where
+    T: Copy
+        + Float
+        + FloatConst
+        + ScalarOperand
+        + NumAssign
+        + std::fmt::Debug
+        + Sync
+        + Send
+        + FftNum,
+    R: Copy
+        + Mul<T, Output = R>
+        + ScalarOperand
+        + NumAssign
+        + std::fmt::Debug
+        + Sum
+        + Default
+        + Sync
+        + Send,
+    Complex<T>: Copy + std::convert::From<R> + Default + ScalarOperand,
+{
+    /// constructor
+    /// * `nstream` - number of streams, which should be positive
+    /// * `nch` - number of channels including both pos and neg ones
+    /// * `coeff` - prototype low-pass filter, the tap of which should be nch times of the tap of each branch.
+    /// * return value - `Analyzer`
+    /// ```
+    /// extern crate rsdsp;
+    /// use num::complex::Complex;
+    /// use ndarray::Array2;
+    /// use rsdsp::{
+    ///     windowed_fir
+    ///     , multi_stream::Analyzer
+    /// };
+    ///
+    /// let nch=32;
+    /// let tap_per_ch=16;
+    /// let coeff=windowed_fir::pfb_coeff::<f64>(nch, tap_per_ch, 1.1);
+    /// let mut pfb=Analyzer::<Complex<f64>, f64>::new(4, nch, coeff.as_slice().unwrap());
+    /// let input_signal=Array2::<Complex<f64>>::zeros((4, 256));
+    /// let channelized_signal=pfb.analyze_par(input_signal.view());
+    /// assert_eq!(channelized_signal.shape(), &[4, nch, 256/nch]);
+    /// ```
+    pub fn new(nstream: usize, nch: usize, coeff: &[T]) -> Self {
+        assert!(nstream > 0, "there should be at least one stream");
+        let tap = coeff.len() / nch;
+        assert!(nch * tap == coeff.len());
+        let coeff = polyphase_decomp(coeff, nch)
+            .t()
+            .slice(s![..;-1, ..])
+            .iter()
+            .cloned()
+            .collect();
+        Self {
+            coeff,
+            nch,
+            state: vec![vec![R::default(); tap * nch]; nstream],
+            head: 0,
+            buffer: vec![Vec::new(); nstream],
+            nframes: 0,
+            fft: FftPlan::forward(nch),
+        }
+    }
+
+    /// return the number of channels
+    pub fn nch(&self) -> usize {
+        self.nch
+    }
+
+    /// return the number of streams
+    pub fn nstream(&self) -> usize {
+        self.state.len()
+    }
+
+    fn tap(&self) -> usize {
+        self.coeff.len() / self.nch
+    }
+
+    pub fn predict_output_length(&self, input_len: usize) -> usize {
+        (self.buffer[0].len() + input_len) / self.nch
+    }
+
+    /// timing of the output frames of every stream, the same as that of [`crate::cspfb::Analyzer`]
+    pub fn timing(&self) -> Timing {
+        Timing::frames(self.nframes, self.nch, self.coeff.len())
+    }
+
+    /// group delay, the same as that of [`crate::cspfb::Analyzer`]
+    pub fn group_delay(&self) -> GroupDelay {
+        GroupDelay::fir(self.coeff.len(), self.nch as f64)
+    }
+
+    /// channelize one stream, writing `batch` frames to `out`, which has `nch` rows
+    #[allow(clippy::too_many_arguments)]
+    fn analyze1(
+        coeff: ArrayView2<T>,
+        state: &mut [R],
+        head: usize,
+        buffer: &mut Vec<R>,
+        x: ArrayView1<R>,
+        fft: &FftPlan<T>,
+        scratch: &mut [Complex<T>],
+        mut out: ArrayViewMut2<Complex<T>>,
+    ) {
+        let (tap, nch) = coeff.dim();
+        let batch = out.ncols();
+        buffer.extend(x.iter().cloned());
+        let signal = ArrayView2::from_shape((batch, nch), &buffer[..batch * nch]).unwrap();
+        let state = ArrayViewMut2::from_shape((tap, nch), state).unwrap();
+        let mut filtered = Array2::<R>::default((batch, nch));
+        filter_block_impl(coeff, state, head, signal, filtered.view_mut());
+        let mut y = filtered.map(|&r| Complex::<T>::from(r));
+        fft.process_with_scratch(y.as_slice_mut().unwrap(), scratch);
+        out.assign(&y.t());
+        buffer.drain(..batch * nch);
+    }
+
+    /// Channelize input signal of all streams
+    /// * `input_signal` - a 2-d array view with `nstream` rows, each containing time domain signal of one stream
+    /// * return value - channelized data, with the shape of `(nstream, nch, batch)`
+    pub fn analyze(&mut self, input_signal: ArrayView2<R>) -> Array3<Complex<T>> {
+        let mut result = Array3::<Complex<T>>::default((
+            self.nstream(),
+            self.nch,
+            self.predict_output_length(input_signal.ncols()),
+        ));
+        self.analyze_into(input_signal, result.view_mut(), false);
+        result
+    }
+
+    /// Parallel version of [`Self::analyze`], the streams are channelized in parallel
+    pub fn analyze_par(&mut self, input_signal: ArrayView2<R>) -> Array3<Complex<T>> {
+        let mut result = Array3::<Complex<T>>::default((
+            self.nstream(),
+            self.nch,
+            self.predict_output_length(input_signal.ncols()),
+        ));
+        self.analyze_into(input_signal, result.view_mut(), true);
+        result
+    }
+
+    fn analyze_into(
+        &mut self,
+        input_signal: ArrayView2<R>,
+        mut out: ArrayViewMut3<Complex<T>>,
+        par: bool,
+    ) {
+        assert_eq!(input_signal.nrows(), self.nstream());
+        let (tap, nch) = (self.tap(), self.nch);
+        let batch = out.shape()[2];
+        let coeff = ArrayView2::from_shape((tap, nch), &self.coeff).unwrap();
+        let head = self.head;
+        self.fft.ensure_len(nch);
+        let fft = &self.fft;
+        let scratch_len = fft.scratch_len();
+        if par {
+            self.state
+                .par_iter_mut()
+                .zip(self.buffer.par_iter_mut())
+                .zip(input_signal.axis_iter(Axis(0)).into_par_iter())
+                .zip(out.axis_iter_mut(Axis(0)).into_par_iter())
+                .for_each_init(
+                    || vec![Complex::<T>::default(); scratch_len],
+                    |scratch, (((state, buffer), x), y)| {
+                        Self::analyze1(coeff, state, head, buffer, x, fft, scratch, y)
+                    },
+                );
+        } else {
+            let mut scratch = vec![Complex::<T>::default(); scratch_len];
+            self.state
+                .iter_mut()
+                .zip(self.buffer.iter_mut())
+                .zip(input_signal.axis_iter(Axis(0)))
+                .zip(out.axis_iter_mut(Axis(0)))
+                .for_each(|(((state, buffer), x), y)| {
+                    Self::analyze1(coeff, state, head, buffer, x, fft, &mut scratch, y)
+                });
+        }
+        self.head = (head + batch) % tap;
+        self.nframes += batch;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cspfb, cspfb::tests::white_noise, windowed_fir::pfb_coeff};
+
+    #[test]
+    fn multi_stream_test() {
+        let nch = 16;
+        let nstream = 3;
+        let coeff = pfb_coeff::<f64>(nch, 8, 1.1);
+        let coeff = coeff.as_slice().unwrap();
+        let signal =
+            Array2::from_shape_vec((nstream, nch * 50), white_noise(nstream * nch * 50, 8))
+                .unwrap();
+
+        let mut pfb = Analyzer::<Complex<f64>, f64>::new(nstream, nch, coeff);
+        let mut pfb_par = pfb.clone();
+        let y = [0, 37, 300, nch * 50]
+            .windows(2)
+            .map(|w| pfb.analyze(signal.slice(s![.., w[0]..w[1]])))
+            .reduce(|a, b| ndarray::concatenate![Axis(2), a, b])
+            .unwrap();
+        let y_par = pfb_par.analyze_par(signal.view());
+        assert_eq!(y.shape(), &[nstream, nch, 50]);
+        assert_eq!(y.shape(), y_par.shape());
+
+        signal.outer_iter().enumerate().for_each(|(i, x)| {
+            let mut pfb1 = cspfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff);
+            let y1 = pfb1.analyze(x.as_slice().unwrap());
+            assert!(y1
+                .iter()
+                .zip(y.index_axis(Axis(0), i).iter())
+                .all(|(a, b)| (a - b).norm() < 1e-12));
+            assert!(y1
+                .iter()
+                .zip(y_par.index_axis(Axis(0), i).iter())
+                .all(|(a, b)| (a - b).norm() < 1e-12));
+        });
+    }
+
+    #[test]
+    fn malformed_serde_test() {
+        let coeff = pfb_coeff::<f64>(4, 2, 1.1);
+        let mut pfb = Analyzer::<Complex<f64>, f64>::new(2, 4, coeff.as_slice().unwrap());
+        pfb.analyze(Array2::from_elem((2, 6), Complex::new(1.0, 0.0)).view());
+        let valid = serde_yaml::to_value(&pfb).unwrap();
+        assert!(serde_yaml::from_value::<Analyzer<Complex<f64>, f64>>(valid.clone()).is_ok());
+
+        let mut values = vec![valid.clone(); 6];
+        // nch not dividing the length of coeff
+        values[0]["nch"] = 3.into();
+        // a state shorter than coeff
+        values[1]["state"][1].as_sequence_mut().unwrap().pop();
+        // head out of range
+        values[2]["head"] = 2.into();
+        // fewer buffers than streams
+        values[3]["buffer"].as_sequence_mut().unwrap().pop();
+        // buffers of different lengths
+        values[4]["buffer"][0].as_sequence_mut().unwrap().pop();
+        // no stream
+        values[5]["state"] = serde_yaml::Value::Sequence(Vec::new());
+        values[5]["buffer"] = serde_yaml::Value::Sequence(Vec::new());
+        for v in values {
+            assert_ne!(v, valid);
+            assert!(serde_yaml::from_value::<Analyzer<Complex<f64>, f64>>(v).is_err());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "there should be at least one stream")]
+    fn no_stream_test() {
+        let coeff = pfb_coeff::<f64>(4, 2, 1.1);
+        Analyzer::<Complex<f64>, f64>::new(0, 4, coeff.as_slice().unwrap());
+    }
+}
@@ -1,8 +1,16 @@
 //! oversampling poly phase filter bank
 
 #![allow(clippy::uninit_vec)]
-use crate::{batch_filter::BatchFilter, oscillator::HalfChShifter, utils::polyphase_decomp};
-use ndarray::{parallel::prelude::*, s, Array1, Array2, Axis, ScalarOperand};
+use crate::{
+    batch_filter::BatchFilter,
+    channelizer::{ChannelFrequency, Channelizer, DataLayout, OutputLayout},
+    oscillator::HalfChShifter,
+    timing::{GroupDelay, Timing},
+    utils::{polyphase_decomp, FftPlan},
+};
+use ndarray::{
+    parallel::prelude::*, s, Array1, Array2, ArrayView2, ArrayViewMut2, Axis, ScalarOperand,
+};
 use num::{
     complex::Complex,
     traits::{Float, FloatConst, NumAssign},
@@ -10,14 +18,14 @@ use num::{
 
 use serde::{Deserialize, Serialize};
 
-use rustfft::{FftNum, FftPlanner};
+use rustfft::FftNum;
 use std::{iter::Sum, ops::Mul};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Analyzer<R, T>
 where
     R: std::fmt::Debug,
-    T: std::fmt::Debug + Float,
+    T: std::fmt::Debug + Float + FftNum,
 {
     /// filters for even channels
     filter_even: BatchFilter<R, T>,
@@ -28,8 +36,28 @@ where
     /// a buffer, ensurning that the input signal length need not to be nch*tap. The remaining elements will be stored and be concated with the input next time.
     buffer: Vec<R>,
 
+    /// number of frames that have been produced, see [`Self::timing`]
+    #[serde(default)]
+    nframes: usize,
+
     /// shifting input signal by half of the channel spacing
     shifter: HalfChShifter<T>,
+
+    /// fft plan of `nch_total/2` points, planned again if missing from the serialized form
+    #[serde(default = "FftPlan::default")]
+    fft: FftPlan<T>,
+
+    /// output of the filters for even channels of one frame
+    #[serde(skip, default = "Vec::new")]
+    frame_filtered: Vec<R>,
+
+    /// spectra of the even channels, the shifted input, and the spectra of the odd channels of one frame
+    #[serde(skip, default = "Vec::new")]
+    frame: Vec<Complex<T>>,
+
+    /// validity of the buffered input, see [`Self::analyze_masked`]
+    #[serde(skip, default = "Vec::new")]
+    mask_buffer: Vec<bool>,
 }
 
 impl<R, T> Analyzer<R, T>
@@ -69,13 +97,13 @@ where
     /// let tap_per_ch=16;
     /// let k=1.1;
     /// let coeff=windowed_fir::pfb_coeff::<f64>(nch/2, tap_per_ch, k);
-    /// let mut pfb=Analyzer::<Complex<f64>, f64>::new(nch, coeff.view());
+    /// let mut pfb=Analyzer::<Complex<f64>, f64>::new(nch, coeff.as_slice().unwrap());
     /// ```
     pub fn new(nch_total: usize, coeff: &[T]) -> Self {
         let nch_each = nch_total / 2;
         let tap = coeff.len() / nch_each;
         assert!(nch_each * tap == coeff.len());
-        let coeff=polyphase_decomp(coeff, nch_each);
+        let coeff = polyphase_decomp(coeff, nch_each);
         let filter_even = BatchFilter::new(coeff.view());
         let filter_odd = BatchFilter::<Complex<T>, T>::new(coeff.view());
 
@@ -85,20 +113,72 @@ where
             filter_even,
             filter_odd,
             buffer: Vec::<R>::new(),
+            nframes: 0,
             shifter,
+            fft: FftPlan::forward(nch_each),
+            frame_filtered: Vec::new(),
+            frame: Vec::new(),
+            mask_buffer: Vec::new(),
         }
     }
 
     pub fn nch_total(&self) -> usize {
-        self.filter_even.coeff[0].len() * 2
+        self.filter_even.nch() * 2
+    }
+
+    /// frequencies of the output channels in the unit of the input sampling rate, one for each row of the output of [`Self::analyze`],
+    /// with the center frequencies in `[-0.5, 0.5)`
+    pub fn normalized_channel_frequencies(&self) -> Vec<ChannelFrequency<T>> {
+        ChannelFrequency::fft_order(self.nch_total(), T::from(2).unwrap())
+    }
+
+    /// physical frequencies of the output channels, see [`Self::normalized_channel_frequencies`]
+    /// * `sample_rate` - sampling rate of the input signal
+    /// * `center_freq` - physical frequency of the DC of the input signal
+    /// ```
+    /// extern crate rsdsp;
+    /// use num::complex::Complex;
+    /// use rsdsp::{
+    ///     windowed_fir
+    ///     , ospfb::Analyzer
+    /// };
+    ///
+    /// let nch=8;
+    /// let coeff=windowed_fir::pfb_coeff::<f64>(nch/2, 16, 1.1);
+    /// let pfb=Analyzer::<Complex<f64>, f64>::new(nch, coeff.as_slice().unwrap());
+    /// let freqs=pfb.channel_frequencies(800e6, 1e9);
+    /// // the same order as fftfreq
+    /// assert_eq!(freqs[1].center, 1.1e9);
+    /// assert_eq!(freqs[7].center, 0.9e9);
+    /// assert_eq!(freqs[7].bandwidth, 100e6);
+    /// // two times oversampled
+    /// assert_eq!(freqs[7].sample_rate, 200e6);
+    /// ```
+    pub fn channel_frequencies(&self, sample_rate: T, center_freq: T) -> Vec<ChannelFrequency<T>> {
+        self.normalized_channel_frequencies()
+            .into_iter()
+            .map(|f| f.to_physical(sample_rate, center_freq))
+            .collect()
     }
 
     pub fn predict_output_length(&self, input_len: usize) -> usize {
-        (self.buffer.len() + input_len) / self.filter_even.coeff[0].len()
+        (self.buffer.len() + input_len) / self.filter_even.nch()
+    }
+
+    /// timing of the output frames, i.e., frame `j` is at `(j+1)*nch_total/2-1-(coeff.len()-1)/2` input samples
+    pub fn timing(&self) -> Timing {
+        let nch_each = self.filter_even.nch();
+        Timing::frames(self.nframes, nch_each, nch_each * self.filter_even.tap())
+    }
+
+    /// group delay, i.e., `(coeff.len()-1)/2` input samples
+    pub fn group_delay(&self) -> GroupDelay {
+        let nch_each = self.filter_even.nch();
+        GroupDelay::fir(nch_each * self.filter_even.tap(), nch_each as f64)
     }
 
     pub fn buffer_input(&mut self, input_signal: &[R]) -> Vec<R> {
-        let nch_each = self.filter_even.coeff[0].len();
+        let nch_each = self.filter_even.nch();
 
         let batch = (self.buffer.len() + input_signal.len()) / nch_each;
 
@@ -122,6 +202,7 @@ where
             .iter_mut()
             .zip(&input_signal[input_signal.len() - l..])
             .for_each(|(a, &b)| *a = b);
+        self.nframes += batch;
         signal
     }
 
@@ -142,14 +223,92 @@ where
     /// let tap_per_ch=16;
     /// let k=1.1;
     /// let coeff=windowed_fir::pfb_coeff::<f64>(nch/2, tap_per_ch, k);
-    /// let mut pfb=Analyzer::<Complex<f64>, f64>::new(nch, coeff.view());
+    /// let mut pfb=Analyzer::<Complex<f64>, f64>::new(nch, coeff.as_slice().unwrap());
     /// let mut osc=COscillator::<f64>::new(0.0, f64::PI()/(nch/2) as f64*4.0);//some certain frequency
     /// let input_signal:Vec<_>=(0..256).map(|_| osc.get()).collect();
     /// let channelized_signal=pfb.analyze(&input_signal);
     /// assert_eq!(channelized_signal.nrows(), nch);
     /// ```
     pub fn analyze(&mut self, input_signal: &[R]) -> Array2<Complex<T>> {
-        let nch_each = self.filter_even.coeff[0].len();
+        let mut result = Array2::<Complex<T>>::default((
+            self.nch_total(),
+            self.predict_output_length(input_signal.len()),
+        ));
+        self.analyze_into(input_signal, result.view_mut(), DataLayout::ChannelMajor);
+        result
+    }
+
+    /// Channelize input signal with a validity mask, see [`crate::cspfb::Analyzer::analyze_masked`].
+    /// An output frame is invalid if any input in its window, i.e., `nch_total/2*tap` samples, is invalid
+    /// * `input_signal` - a 1-d slice containing time domain input signal
+    /// * `mask` - validity of each input sample, `true` for valid ones
+    /// * return value - the same output as [`Self::analyze`], and the validity of each output frame
+    pub fn analyze_masked(
+        &mut self,
+        input_signal: &[R],
+        mask: &[bool],
+    ) -> (Array2<Complex<T>>, Vec<bool>) {
+        assert_eq!(input_signal.len(), mask.len());
+        let frame_mask =
+            self.filter_even
+                .feed_mask_buffered(&mut self.mask_buffer, self.buffer.len(), mask);
+        // both filters are fed with the same time steps
+        self.filter_odd.valid_run = self.filter_even.valid_run;
+        (self.analyze(input_signal), frame_mask)
+    }
+
+    /// Channelize input signal into a caller-provided buffer
+    /// * `input_signal` - a 1-d slice containing time domain input signal
+    /// * `out` - output buffer, the shape of which should be `layout.shape(nch_total, predict_output_length(input_signal.len()))`
+    /// * `layout` - whether `out` is channel-major, as the output of [`Self::analyze`], or time-major
+    pub fn analyze_into(
+        &mut self,
+        input_signal: &[R],
+        mut out: ArrayViewMut2<Complex<T>>,
+        layout: DataLayout,
+    ) {
+        let nch_each = self.filter_even.nch();
+        let batch = self.predict_output_length(input_signal.len());
+        assert_eq!(out.shape(), &layout.shape(nch_each * 2, batch));
+
+        self.buffer.extend_from_slice(input_signal);
+        self.frame_filtered.resize(nch_each, R::default());
+        self.frame.resize(nch_each * 3, Complex::<T>::default());
+        let (frame_even, frame) = self.frame.split_at_mut(nch_each);
+        let (frame_shifted, frame_odd) = frame.split_at_mut(nch_each);
+        self.fft.ensure_len(nch_each);
+
+        self.buffer
+            .chunks_exact(nch_each)
+            .zip(out.axis_iter_mut(layout.frame_axis()))
+            .for_each(|(x, mut y)| {
+                self.filter_even.filter_into(x, &mut self.frame_filtered);
+                frame_even
+                    .iter_mut()
+                    .zip(&self.frame_filtered)
+                    .for_each(|(a, &b)| *a = Complex::<T>::from(b));
+                frame_shifted
+                    .iter_mut()
+                    .zip(x)
+                    .for_each(|(a, &b)| *a = Complex::<T>::from(b) * self.shifter.get());
+                self.filter_odd.filter_into(frame_shifted, frame_odd);
+                self.fft.process(frame_even);
+                self.fft.process(frame_odd);
+                y.iter_mut()
+                    .zip(
+                        frame_even
+                            .iter()
+                            .zip(frame_odd.iter())
+                            .flat_map(|(e, o)| [e, o]),
+                    )
+                    .for_each(|(a, &b)| *a = b);
+            });
+        self.buffer.drain(..batch * nch_each);
+        self.nframes += batch;
+    }
+
+    pub fn analyze_par(&mut self, input_signal: &[R]) -> Array2<Complex<T>> {
+        let nch_each = self.filter_even.nch();
 
         let nch_total = nch_each * 2;
 
@@ -177,100 +336,328 @@ where
             .axis_iter_mut(Axis(0))
             .zip(signal.chunks(nch_each))
             .for_each(|(mut result1, x_even)| {
-                let y_even =
-                    Array1::from(self.filter_even.filter(x_even)).map(|&r| Complex::<T>::from(r));
+                let y_even = Array1::from(self.filter_even.filter_par(x_even))
+                    .map(|&r| Complex::<T>::from(r));
                 result1.assign(&y_even);
             });
         result_odd
             .axis_iter_mut(Axis(0))
             .zip(signal_shifted.chunks(nch_each))
             .for_each(|(mut result1, x_odd)| {
-                let y_odd = Array1::from(self.filter_odd.filter(x_odd));
-                result1.assign(&y_odd);
+                self.filter_odd
+                    .filter_into_par(x_odd, result1.as_slice_mut().unwrap());
             });
 
-        //let mut fft_plan=CFFT::<T>::with_len(x1.shape()[0]);
-        let mut planner = FftPlanner::new();
-        let fft = planner.plan_fft_forward(nch_each);
+        self.fft.ensure_len(nch_each);
+        self.fft.process_par(result_even.as_slice_mut().unwrap());
+        self.fft.process_par(result_odd.as_slice_mut().unwrap());
         result
             .axis_iter_mut(Axis(0))
+            .into_par_iter()
             .zip(
                 result_even
-                    .axis_iter_mut(Axis(0))
-                    .zip(result_odd.axis_iter_mut(Axis(0))),
+                    .axis_iter(Axis(0))
+                    .into_par_iter()
+                    .zip(result_odd.axis_iter(Axis(0)).into_par_iter()),
             )
-            .for_each(|(mut result1, (mut re, mut ro))| {
-                fft.process(re.as_slice_mut().unwrap());
-                fft.process(ro.as_slice_mut().unwrap());
-
+            .for_each(|(mut result1, (re, ro))| {
                 result1.slice_mut(s![0_usize..;2]).assign(&re);
                 result1.slice_mut(s![1_usize..;2]).assign(&ro);
             });
 
         result.t().as_standard_layout().to_owned()
     }
+}
 
-    pub fn analyze_par(&mut self, input_signal: &[R]) -> Array2<Complex<T>> {
-        let nch_each = self.filter_even.coeff[0].len();
+impl<R, T> Channelizer for Analyzer<R, T>
+where
+    T: Copy
+        + Float
+        + FloatConst
+        + ScalarOperand
+        + NumAssign
+        + std::fmt::Debug
+        + Sync
+        + Send
+        + FftNum,
+    R: Copy
+        + Mul<T, Output = R>
+        + Default
+        + ScalarOperand
+        + NumAssign
+        + std::fmt::Debug
+        + Sum
+        + Sync
+        + Send,
+    Complex<T>: Copy + std::convert::From<R> + Sum + Default + ScalarOperand,
+{
+    type Input = [R];
+    type Output = Complex<T>;
 
-        let nch_total = nch_each * 2;
+    fn nch(&self) -> usize {
+        self.nch_total()
+    }
 
-        let batch = (self.buffer.len() + input_signal.len()) / nch_each;
-        assert_eq!(batch, self.predict_output_length(input_signal.len()));
+    fn output_layout(&self) -> OutputLayout {
+        OutputLayout::FftOrder
+    }
 
-        let signal = self.buffer_input(input_signal);
-        let signal_shifted = signal
-            .iter()
-            .map(|&x| Complex::<T>::from(x) * self.shifter.get())
-            .collect::<Vec<_>>();
+    fn predict_output_length(&self, input_len: usize) -> usize {
+        Analyzer::predict_output_length(self, input_len)
+    }
 
-        //let mut x1 = self.filter_even.filter(signal.view());
-        //let mut x2 = self.filter_odd.filter(signal_shifted.view());
+    fn timing(&self) -> Timing {
+        Analyzer::timing(self)
+    }
 
-        let mut result_even =
-            unsafe { Array2::<Complex<T>>::uninit((batch, nch_each)).assume_init() };
+    fn group_delay(&self) -> GroupDelay {
+        Analyzer::group_delay(self)
+    }
 
-        let mut result_odd =
-            unsafe { Array2::<Complex<T>>::uninit((batch, nch_each)).assume_init() };
+    fn analyze(&mut self, input: &[R]) -> Array2<Complex<T>> {
+        Analyzer::analyze(self, input)
+    }
 
-        let mut result = unsafe { Array2::<Complex<T>>::uninit((batch, nch_total)).assume_init() };
+    fn analyze_par(&mut self, input: &[R]) -> Array2<Complex<T>> {
+        Analyzer::analyze_par(self, input)
+    }
 
-        result_even
-            .axis_iter_mut(Axis(0))
-            .zip(signal.chunks(nch_each))
-            .for_each(|(mut result1, x_even)| {
-                let y_even = Array1::from(self.filter_even.filter_par(x_even))
-                    .map(|&r| Complex::<T>::from(r));
-                result1.assign(&y_even);
-            });
-        result_odd
-            .axis_iter_mut(Axis(0))
-            .zip(signal_shifted.chunks(nch_each))
-            .for_each(|(mut result1, x_odd)| {
-                let y_odd = Array1::from(self.filter_odd.filter_par(x_odd));
-                result1.assign(&y_odd);
-            });
+    fn analyze_into(&mut self, input: &[R], out: ArrayViewMut2<Complex<T>>, layout: DataLayout) {
+        Analyzer::analyze_into(self, input, out, layout)
+    }
+}
+
+/// Synthesis filter bank, the inverse of [`Analyzer`]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Synthesizer<T>
+where
+    T: std::fmt::Debug + Float + FftNum,
+{
+    /// synthesis filters for even channels
+    filter_even: BatchFilter<Complex<T>, T>,
+
+    /// synthesis filters for odd channels
+    filter_odd: BatchFilter<Complex<T>, T>,
+
+    /// shifting the odd channels upward by half of the channel spacing
+    shifter: HalfChShifter<T>,
+
+    /// inverse fft plan
+    ifft: FftPlan<T>,
+
+    /// delay of the reconstructed signal relative to the input of the [`Analyzer`]
+    group_delay: usize,
+
+    /// rms reconstruction error of the analysis-synthesis chain
+    reconstruction_error: T,
+}
+
+impl<T> Synthesizer<T>
+where
+    T: Copy
+        + Float
+        + FloatConst
+        + ScalarOperand
+        + NumAssign
+        + std::fmt::Debug
+        + Sync
+        + Send
+        + FftNum,
+    Complex<T>: Copy + Sum + Default + ScalarOperand,
+{
+    /// constructor
+    /// * `nch_total` - total number of channels, including even and odd, pos and neg channels
+    /// * `coeff` - prototype low pass filter of the [`Analyzer`]
+    /// * `coeff_syn` - prototype low pass filter for synthesis, the length of which should be a multiple of `nch_total`/2.
+    ///   Its pass band should be as wide as the channel spacing, with the transition band symmetric about the edge, e.g.,
+    ///   `windowed_fir::pfb_coeff(nch_total, tap_per_ch, 1+1/tap_per_ch)`.
+    ///   The total length of `coeff` and `coeff_syn` should be a multiple of `nch_total`, so that the delay of the chain is a whole number of hops.
+    /// ```
+    /// extern crate rsdsp;
+    /// use num::complex::Complex;
+    /// use rsdsp::{
+    ///     windowed_fir
+    ///     , ospfb::{Analyzer, Synthesizer}
+    /// };
+    ///
+    /// let nch=32;
+    /// let tap_per_ch=16;
+    /// let coeff=windowed_fir::pfb_coeff::<f64>(nch/2, tap_per_ch, 1.1);
+    /// let coeff_syn=windowed_fir::pfb_coeff::<f64>(nch, tap_per_ch, 1.0+1.0/tap_per_ch as f64);
+    /// let mut ana=Analyzer::<Complex<f64>, f64>::new(nch, coeff.as_slice().unwrap());
+    /// let mut syn=Synthesizer::<f64>::new(nch, coeff.as_slice().unwrap(), coeff_syn.as_slice().unwrap());
+    /// let input_signal=vec![Complex::<f64>::new(1.0, 0.0); 4096];
+    /// let output_signal=syn.synthesize(ana.analyze(&input_signal).view());
+    /// assert_eq!(output_signal.len(), input_signal.len());
+    /// let d=syn.group_delay();
+    /// assert!((output_signal[d+100]-input_signal[100]).norm()<1e-2);
+    /// ```
+    pub fn new(nch_total: usize, coeff: &[T], coeff_syn: &[T]) -> Self {
+        let nch_each = nch_total / 2;
+        let tap = coeff_syn.len() / nch_each;
+        assert!(nch_each * tap == coeff_syn.len());
+        assert_eq!((coeff.len() + coeff_syn.len()) % nch_total, 0);
+        let (lag, gain, reconstruction_error) = chain_response(nch_each, coeff, coeff_syn);
+        let coeff_syn = polyphase_decomp(coeff_syn, nch_each)
+            .slice(s![.., ..;-1])
+            .map(|&x| x * gain);
+        let filter_even = BatchFilter::new(coeff_syn.view());
+        let filter_odd = BatchFilter::new(coeff_syn.view());
+
+        let mut shifter = HalfChShifter::<T>::new(nch_each, true);
+        // odd channels should be in phase with even ones at the delay of the chain
+        (0..(lag % 2) * nch_each).for_each(|_| {
+            shifter.get();
+        });
+
+        Self {
+            filter_even,
+            filter_odd,
+            shifter,
+            ifft: FftPlan::inverse(nch_each),
+            group_delay: lag * nch_each,
+            reconstruction_error,
+        }
+    }
+
+    /// return the number of channels, i.e., `nch_total` of the [`Analyzer`]
+    pub fn nch_total(&self) -> usize {
+        self.filter_even.nch() * 2
+    }
+
+    /// delay of the reconstructed signal relative to the input of the [`Analyzer`], in samples
+    pub fn group_delay(&self) -> usize {
+        self.group_delay
+    }
+
+    /// relative rms reconstruction error for a white noise input, see [`chain_response`]
+    pub fn reconstruction_error(&self) -> T {
+        self.reconstruction_error
+    }
 
-        //let mut fft_plan=CFFT::<T>::with_len(x1.shape()[0]);
-        let mut planner = FftPlanner::new();
-        let fft = planner.plan_fft_forward(nch_each);
+    /// Reconstruct the time domain signal from channelized data
+    /// * `x` - channelized data, with `nch_total` rows, as returned by [`Analyzer::analyze`]
+    /// * return value - time domain signal, the length of which is `nch_total`/2 times of the number of columns of `x`
+    pub fn synthesize(&mut self, x: ArrayView2<Complex<T>>) -> Vec<Complex<T>> {
+        let nch_each = self.filter_even.nch();
+        assert_eq!(x.nrows(), nch_each * 2);
+        let mut buf_even = vec![Complex::<T>::default(); nch_each];
+        let mut buf_odd = vec![Complex::<T>::default(); nch_each];
+        let mut result = Vec::with_capacity(nch_each * x.ncols());
+        x.axis_iter(Axis(1)).for_each(|x1| {
+            buf_even
+                .iter_mut()
+                .zip(x1.slice(s![0_usize..;2]))
+                .for_each(|(a, &b)| *a = b);
+            buf_odd
+                .iter_mut()
+                .zip(x1.slice(s![1_usize..;2]))
+                .for_each(|(a, &b)| *a = b);
+            self.ifft.process(&mut buf_even);
+            self.ifft.process(&mut buf_odd);
+            let y_even = self.filter_even.filter(&buf_even);
+            let y_odd = self.filter_odd.filter(&buf_odd);
+            result.extend(
+                y_even
+                    .iter()
+                    .zip(y_odd.iter())
+                    .map(|(&a, &b)| a + b * self.shifter.get()),
+            );
+        });
         result
-            .axis_iter_mut(Axis(0))
-            .into_par_iter()
-            .zip(
-                result_even
-                    .axis_iter_mut(Axis(0))
-                    .into_par_iter()
-                    .zip(result_odd.axis_iter_mut(Axis(0)).into_par_iter()),
-            )
-            .for_each(|(mut result1, (mut re, mut ro))| {
-                fft.process(re.as_slice_mut().unwrap());
-                fft.process(ro.as_slice_mut().unwrap());
+    }
+}
 
-                result1.slice_mut(s![0_usize..;2]).assign(&re);
-                result1.slice_mut(s![1_usize..;2]).assign(&ro);
-            });
+/// Response of an analysis-synthesis chain with 2x oversampling, shared by [`Synthesizer`] and [`crate::ospfb2::Synthesizer`].
+/// With `m`=`nch_each` being the hop length, the output sample with phase `q` is `sum_j c_j(q) x[n-j*m]`,
+/// where `c_j(q)` is proportional to `sum_b g[q+b*m] h[(j+1)*m-1-q-b*m]` for `j` with the same parity as the dominant lag `j0`,
+/// and vanishes otherwise. `h` is `coeff` and `g` is the time reversed `coeff_syn`.
+/// * return value - (`j0`, the gain that normalizes `c_j0` to unity, the rms error `sqrt(mean_q sum_j |c_j(q) - delta_{j, j0}|^2)`)
+pub fn chain_response<T>(nch_each: usize, coeff: &[T], coeff_syn: &[T]) -> (usize, T, T)
+where
+    T: Float,
+{
+    let len = coeff.len();
+    let len_syn = coeff_syn.len();
+    let nlag = (len + len_syn) / nch_each;
+    let c = Array2::from_shape_fn((nch_each, nlag), |(q, j)| {
+        (q..len_syn)
+            .step_by(nch_each)
+            .filter(|&i| (j + 1) * nch_each > i && (j + 1) * nch_each - 1 - i < len)
+            .fold(T::zero(), |a, i| {
+                a + coeff_syn[len_syn - 1 - i] * coeff[(j + 1) * nch_each - 1 - i]
+            })
+    });
+    let c_sum = c.sum_axis(Axis(0));
+    let lag = (0..nlag)
+        .max_by(|&a, &b| c_sum[a].abs().partial_cmp(&c_sum[b].abs()).unwrap())
+        .unwrap();
+    let gain = T::one() / (c_sum[lag] + c_sum[lag]);
+    let norm = T::from(nch_each).unwrap() / c_sum[lag];
+    let err2 = c
+        .indexed_iter()
+        .filter(|&((_, j), _)| j % 2 == lag % 2)
+        .fold(T::zero(), |a, ((_, j), &x)| {
+            let e = if j == lag {
+                x * norm - T::one()
+            } else {
+                x * norm
+            };
+            a + e * e
+        });
+    (lag, gain, (err2 / T::from(nch_each).unwrap()).sqrt())
+}
 
-        result.t().as_standard_layout().to_owned()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cspfb::tests::white_noise, windowed_fir::pfb_coeff};
+
+    #[test]
+    fn synthesizer_reconstruction_test() {
+        let nch = 64;
+        let tap_syn = 16;
+        // lag of the chain is odd for 16 and even for 14
+        for tap_per_ch in [16, 14] {
+            let coeff = pfb_coeff::<f64>(nch / 2, tap_per_ch, 1.1);
+            let coeff_syn = pfb_coeff::<f64>(nch, tap_syn, 1.0 + 1.0 / tap_syn as f64);
+            let mut ana = Analyzer::<Complex<f64>, f64>::new(nch, coeff.as_slice().unwrap());
+            let mut syn = Synthesizer::<f64>::new(
+                nch,
+                coeff.as_slice().unwrap(),
+                coeff_syn.as_slice().unwrap(),
+            );
+
+            let signal = white_noise(nch * 2048, 2);
+            let mut output = Vec::new();
+            for x in signal.chunks(1000) {
+                output.extend(syn.synthesize(ana.analyze(x).view()));
+            }
+            let d = syn.group_delay();
+            let (e2, s2) = output[d..]
+                .iter()
+                .zip(&signal)
+                .fold((0.0, 0.0), |(e, s), (a, b)| {
+                    (e + (a - b).norm_sqr(), s + b.norm_sqr())
+                });
+            let err = (e2 / s2).sqrt();
+            assert!(syn.reconstruction_error() < 1e-3);
+            assert!((err - syn.reconstruction_error()).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn baseline_serde_test() {
+        // an analyzer fed with `1..=6` and saved before the fft plan was cached, which is planned again
+        let baseline = "filter_even:\n  coeff:\n  - - 8.0\n    - 7.0\n    - 6.0\n    - 5.0\n  - - 4.0\n    - 3.0\n    - 2.0\n    - 1.0\n  state:\n  - - - 0.0\n      - 0.0\n    - - 0.0\n      - 0.0\n    - - 0.0\n      - 0.0\n    - - 0.0\n      - 0.0\n  - - - 1.0\n      - 0.0\n    - - 2.0\n      - 0.0\n    - - 3.0\n      - 0.0\n    - - 4.0\n      - 0.0\n  head: 1\nfilter_odd:\n  coeff:\n  - - 8.0\n    - 7.0\n    - 6.0\n    - 5.0\n  - - 4.0\n    - 3.0\n    - 2.0\n    - 1.0\n  state:\n  - - - 0.0\n      - 0.0\n    - - 0.0\n      - 0.0\n    - - 0.0\n      - 0.0\n    - - 0.0\n      - 0.0\n  - - - 1.0\n      - 0.0\n    - - 1.4142135623730951\n      - -1.414213562373095\n    - - 1.8369701987210297e-16\n      - -3.0\n    - - -2.82842712474619\n      - -2.8284271247461903\n  head: 1\nbuffer:\n- - 5.0\n  - 0.0\n- - 6.0\n  - 0.0\nshifter:\n  nch: 4\n  factor:\n  - - 1.0\n    - 0.0\n  - - 0.7071067811865476\n    - -0.7071067811865475\n  - - 6.123233995736766e-17\n    - -1.0\n  - - -0.7071067811865475\n    - -0.7071067811865476\n  - - -1.0\n    - -1.2246467991473532e-16\n  - - -0.7071067811865477\n    - 0.7071067811865475\n  - - -1.8369701987210297e-16\n    - 1.0\n  - - 0.7071067811865474\n    - 0.7071067811865477\n  idx: 4\n";
+        let coeff: Vec<f64> = (1..=8).map(|i| i as f64).collect();
+        let signal: Vec<_> = (1..=30).map(|i| Complex::new(i as f64, 0.0)).collect();
+        let mut pfb1 = Analyzer::<Complex<f64>, f64>::new(8, &coeff);
+        pfb1.analyze(&signal[..6]);
+        let mut pfb2: Analyzer<Complex<f64>, f64> = serde_yaml::from_str(baseline).unwrap();
+        let y1 = pfb1.analyze(&signal[6..]);
+        let y2 = pfb2.analyze(&signal[6..]);
+        assert_eq!(y1.shape(), y2.shape());
+        assert!(y1.iter().zip(y2.iter()).all(|(a, b)| (a - b).norm() < 1e-9));
     }
 }
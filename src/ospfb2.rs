@@ -1,8 +1,15 @@
 //! oversampling poly phase filter bank
 
 #![allow(clippy::uninit_vec)]
-use crate::cspfb;
-use ndarray::{s, Array2, Axis, ScalarOperand};
+use crate::{
+    batch_filter::BatchFilter,
+    channelizer::{ChannelFrequency, Channelizer, DataLayout, OutputLayout},
+    cspfb,
+    ospfb::chain_response,
+    timing::{GroupDelay, Timing},
+    utils::{polyphase_decomp, FftPlan},
+};
+use ndarray::{s, Array2, ArrayView2, ArrayViewMut2, Axis, ScalarOperand, Slice};
 use num::{
     complex::Complex,
     traits::{Float, FloatConst, NumAssign},
@@ -10,14 +17,14 @@ use num::{
 
 use serde::{Deserialize, Serialize};
 
-use rustfft::{FftNum};
+use rustfft::FftNum;
 use std::{iter::Sum, ops::Mul};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Analyzer<R, T>
 where
     R: std::fmt::Debug,
-    T: std::fmt::Debug + Float,
+    T: std::fmt::Debug + Float + FftNum,
 {
     /// filters for even channels
     pfb_even: cspfb::Analyzer<R, T>,
@@ -63,7 +70,7 @@ where
     /// let tap_per_ch=16;
     /// let k=1.1;
     /// let coeff=windowed_fir::pfb_coeff::<f64>(nch/2, tap_per_ch, k);
-    /// let mut pfb=Analyzer::<Complex<f64>, f64>::new(nch, coeff.view());
+    /// let mut pfb=Analyzer::<Complex<f64>, f64>::new(nch, coeff.as_slice().unwrap());
     /// ```
     pub fn new(nch: usize, coeff: &[T]) -> Self {
         let tap = coeff.len() / nch;
@@ -82,6 +89,49 @@ where
         self.pfb_even.nch() * 2
     }
 
+    /// frequencies of the output channels in the unit of the input sampling rate, one for each row of the output of [`Self::analyze`],
+    /// with the center frequencies in `[-0.5, 0.5)`
+    pub fn normalized_channel_frequencies(&self) -> Vec<ChannelFrequency<T>> {
+        ChannelFrequency::fft_order(self.pfb_even.nch(), T::from(2).unwrap())
+    }
+
+    /// physical frequencies of the output channels, see [`Self::normalized_channel_frequencies`]
+    /// * `sample_rate` - sampling rate of the input signal
+    /// * `center_freq` - physical frequency of the DC of the input signal
+    pub fn channel_frequencies(&self, sample_rate: T, center_freq: T) -> Vec<ChannelFrequency<T>> {
+        self.normalized_channel_frequencies()
+            .into_iter()
+            .map(|f| f.to_physical(sample_rate, center_freq))
+            .collect()
+    }
+
+    /// number of output frames, i.e., columns of the output of [`Self::analyze`], for an input of `input_len` samples
+    pub fn predict_output_length(&self, input_len: usize) -> usize {
+        self.pfb_odd.predict_output_length(input_len) * 2
+    }
+
+    /// timing of the output frames, i.e., frame `k` is at `(k+1)*nch/2-1-(coeff.len()-1)/2` input samples
+    pub fn timing(&self) -> Timing {
+        // the input of the even channels is preceded by `nch/2` zeros,
+        // so that the even frames are half a hop earlier than the odd ones, and the odd frames are the 2nd, 4th, ... ones
+        let t = self.pfb_odd.timing();
+        let hop = self.pfb_odd.nch() / 2;
+        Timing {
+            next_output: t.next_output * 2,
+            offset: t.offset - hop as f64,
+            step: hop as f64,
+        }
+    }
+
+    /// group delay, i.e., `(coeff.len()-1)/2` input samples, the same as that of the filter bank of each parity
+    pub fn group_delay(&self) -> GroupDelay {
+        let gd = self.pfb_odd.group_delay();
+        GroupDelay {
+            input: gd.input,
+            output: gd.output * 2.0,
+        }
+    }
+
     pub fn analyze_raw(&mut self, input_signal: &[R]) -> Array2<Complex<T>> {
         let y_even = self.pfb_even.analyze_raw(input_signal);
         let mut y_odd = self.pfb_odd.analyze_raw(input_signal);
@@ -104,7 +154,62 @@ where
     }
 
     pub fn analyze(&mut self, input_signal: &[R]) -> Array2<Complex<T>> {
-        self.analyze_raw(input_signal).t().as_standard_layout().to_owned()
+        let mut result = Array2::<Complex<T>>::default((
+            self.pfb_even.nch(),
+            self.predict_output_length(input_signal.len()),
+        ));
+        self.analyze_into(input_signal, result.view_mut(), DataLayout::ChannelMajor);
+        result
+    }
+
+    /// Channelize input signal with a validity mask, see [`cspfb::Analyzer::analyze_masked`].
+    /// An output frame is invalid if any input in its window, i.e., `nch*tap` samples, is invalid
+    /// * `input_signal` - a 1-d slice containing time domain input signal
+    /// * `mask` - validity of each input sample, `true` for valid ones
+    /// * return value - the same output as [`Self::analyze`], and the validity of each output frame
+    pub fn analyze_masked(
+        &mut self,
+        input_signal: &[R],
+        mask: &[bool],
+    ) -> (Array2<Complex<T>>, Vec<bool>) {
+        assert_eq!(input_signal.len(), mask.len());
+        let mask_even = self.pfb_even.propagate_mask(mask);
+        let mask_odd = self.pfb_odd.propagate_mask(mask);
+        let frame_mask = mask_even
+            .iter()
+            .zip(&mask_odd)
+            .flat_map(|(&e, &o)| [e, o])
+            .collect();
+        (self.analyze(input_signal), frame_mask)
+    }
+
+    /// Channelize input signal into a caller-provided buffer
+    /// * `input_signal` - a 1-d slice containing time domain input signal
+    /// * `out` - output buffer, the shape of which should be `layout.shape(nch, predict_output_length(input_signal.len()))`
+    /// * `layout` - whether `out` is channel-major, as the output of [`Self::analyze`], or time-major, as that of [`Self::analyze_raw`]
+    pub fn analyze_into(
+        &mut self,
+        input_signal: &[R],
+        mut out: ArrayViewMut2<Complex<T>>,
+        layout: DataLayout,
+    ) {
+        let shape = layout.shape(
+            self.pfb_even.nch(),
+            self.predict_output_length(input_signal.len()),
+        );
+        assert_eq!(out.shape(), &shape);
+        let (frame_axis, ch_axis) = (layout.frame_axis(), layout.channel_axis());
+        self.pfb_even.analyze_into(
+            input_signal,
+            out.slice_axis_mut(frame_axis, Slice::new(0, None, 2)),
+            layout,
+        );
+        let mut y_odd = out.slice_axis_mut(frame_axis, Slice::new(1, None, 2));
+        self.pfb_odd
+            .analyze_into(input_signal, y_odd.view_mut(), layout);
+        y_odd
+            .slice_axis_mut(ch_axis, Slice::new(1, None, 2))
+            .map_inplace(|x| *x = -*x);
     }
 
     pub fn analyze_raw_par(&mut self, input_signal: &[R]) -> Array2<Complex<T>> {
@@ -129,6 +234,253 @@ where
     }
 
     pub fn analyze_par(&mut self, input_signal: &[R]) -> Array2<Complex<T>> {
-        self.analyze_raw_par(input_signal).t().as_standard_layout().to_owned()
+        self.analyze_raw_par(input_signal)
+            .t()
+            .as_standard_layout()
+            .to_owned()
+    }
+}
+
+impl<R, T> Channelizer for Analyzer<R, T>
+where
+    T: Copy
+        + Float
+        + FloatConst
+        + ScalarOperand
+        + NumAssign
+        + std::fmt::Debug
+        + Sync
+        + Send
+        + FftNum,
+    R: Copy
+        + Mul<T, Output = R>
+        + Default
+        + ScalarOperand
+        + NumAssign
+        + std::fmt::Debug
+        + Sum
+        + Sync
+        + Send,
+    Complex<T>: Copy + std::convert::From<R> + Sum + Default + ScalarOperand,
+{
+    type Input = [R];
+    type Output = Complex<T>;
+
+    fn nch(&self) -> usize {
+        self.pfb_even.nch()
+    }
+
+    fn output_layout(&self) -> OutputLayout {
+        OutputLayout::FftOrder
+    }
+
+    fn predict_output_length(&self, input_len: usize) -> usize {
+        Analyzer::predict_output_length(self, input_len)
+    }
+
+    fn timing(&self) -> Timing {
+        Analyzer::timing(self)
+    }
+
+    fn group_delay(&self) -> GroupDelay {
+        Analyzer::group_delay(self)
+    }
+
+    fn analyze(&mut self, input: &[R]) -> Array2<Complex<T>> {
+        Analyzer::analyze(self, input)
+    }
+
+    fn analyze_par(&mut self, input: &[R]) -> Array2<Complex<T>> {
+        Analyzer::analyze_par(self, input)
+    }
+
+    fn analyze_into(&mut self, input: &[R], out: ArrayViewMut2<Complex<T>>, layout: DataLayout) {
+        Analyzer::analyze_into(self, input, out, layout)
+    }
+}
+
+/// Synthesis filter bank, the inverse of [`Analyzer`]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Synthesizer<T>
+where
+    T: std::fmt::Debug + Float + FftNum,
+{
+    /// synthesis filters for frames from the even pfb
+    filter_even: BatchFilter<Complex<T>, T>,
+
+    /// synthesis filters for frames from the odd pfb
+    filter_odd: BatchFilter<Complex<T>, T>,
+
+    /// delaying the output of the odd frames by half of a frame
+    buffer: Vec<Complex<T>>,
+
+    /// whether the sign flipping of odd channels is undone in the even (`true`) or the odd (`false`) frames
+    flip_even: bool,
+
+    /// inverse fft plan
+    ifft: FftPlan<T>,
+
+    /// delay of the reconstructed signal relative to the input of the [`Analyzer`]
+    group_delay: usize,
+
+    /// rms reconstruction error of the analysis-synthesis chain
+    reconstruction_error: T,
+}
+
+impl<T> Synthesizer<T>
+where
+    T: Copy
+        + Float
+        + FloatConst
+        + ScalarOperand
+        + NumAssign
+        + std::fmt::Debug
+        + Sync
+        + Send
+        + FftNum,
+    Complex<T>: Copy + Sum + Default + ScalarOperand,
+{
+    /// constructor
+    /// * `nch` - number of channels
+    /// * `coeff` - prototype low pass filter of the [`Analyzer`]
+    /// * `coeff_syn` - prototype low pass filter for synthesis, the length of which should be a multiple of `nch`.
+    ///   Its pass band should be as wide as the channel spacing, with the transition band symmetric about the edge, e.g.,
+    ///   `windowed_fir::pfb_coeff(nch, tap_per_ch, 1+1/tap_per_ch)`.
+    /// ```
+    /// extern crate rsdsp;
+    /// use num::complex::Complex;
+    /// use rsdsp::{
+    ///     windowed_fir
+    ///     , ospfb2::{Analyzer, Synthesizer}
+    /// };
+    ///
+    /// let nch=32;
+    /// let tap_per_ch=16;
+    /// let coeff=windowed_fir::pfb_coeff::<f64>(nch, tap_per_ch, 1.55*1.1);
+    /// let coeff_syn=windowed_fir::pfb_coeff::<f64>(nch, tap_per_ch, 1.0+1.0/tap_per_ch as f64);
+    /// let mut ana=Analyzer::<Complex<f64>, f64>::new(nch, coeff.as_slice().unwrap());
+    /// let mut syn=Synthesizer::<f64>::new(nch, coeff.as_slice().unwrap(), coeff_syn.as_slice().unwrap());
+    /// let input_signal=vec![Complex::<f64>::new(1.0, 0.0); 4096];
+    /// let output_signal=syn.synthesize(ana.analyze(&input_signal).view());
+    /// assert_eq!(output_signal.len(), input_signal.len());
+    /// let d=syn.group_delay();
+    /// assert!((output_signal[d+100]-input_signal[100]).norm()<1e-2);
+    /// ```
+    pub fn new(nch: usize, coeff: &[T], coeff_syn: &[T]) -> Self {
+        let tap = coeff_syn.len() / nch;
+        assert!(nch * tap == coeff_syn.len());
+        let (lag, gain, reconstruction_error) = chain_response(nch / 2, coeff, coeff_syn);
+        let coeff_syn = polyphase_decomp(coeff_syn, nch)
+            .slice(s![.., ..;-1])
+            .map(|&x| x * gain);
+        let filter_even = BatchFilter::new(coeff_syn.view());
+        let filter_odd = BatchFilter::new(coeff_syn.view());
+
+        Self {
+            filter_even,
+            filter_odd,
+            buffer: vec![Complex::<T>::default(); nch / 2],
+            flip_even: lag % 2 == 0,
+            ifft: FftPlan::inverse(nch),
+            group_delay: lag * nch / 2,
+            reconstruction_error,
+        }
+    }
+
+    /// return the number of channels, i.e., `nch` of the [`Analyzer`]
+    pub fn nch(&self) -> usize {
+        self.filter_even.nch()
+    }
+
+    /// delay of the reconstructed signal relative to the input of the [`Analyzer`], in samples
+    pub fn group_delay(&self) -> usize {
+        self.group_delay
+    }
+
+    /// relative rms reconstruction error for a white noise input, see [`chain_response`]
+    pub fn reconstruction_error(&self) -> T {
+        self.reconstruction_error
+    }
+
+    /// Reconstruct the time domain signal from channelized data
+    /// * `x` - channelized data, with `nch` rows and even number of columns, as returned by [`Analyzer::analyze`]
+    /// * return value - time domain signal, the length of which is `nch`/2 times of the number of columns of `x`
+    pub fn synthesize(&mut self, x: ArrayView2<Complex<T>>) -> Vec<Complex<T>> {
+        let nch = self.nch();
+        assert_eq!(x.nrows(), nch);
+        assert_eq!(x.ncols() % 2, 0);
+        let mut buf_even = vec![Complex::<T>::default(); nch];
+        let mut buf_odd = vec![Complex::<T>::default(); nch];
+        let mut result = Vec::with_capacity(nch / 2 * x.ncols());
+        x.axis_iter(Axis(1))
+            .step_by(2)
+            .zip(x.axis_iter(Axis(1)).skip(1).step_by(2))
+            .for_each(|(x_even, x_odd)| {
+                buf_even
+                    .iter_mut()
+                    .zip(x_even.iter())
+                    .zip(buf_odd.iter_mut().zip(x_odd.iter()))
+                    .enumerate()
+                    .for_each(|(i, ((a_even, &b_even), (a_odd, &b_odd)))| {
+                        *a_even = b_even;
+                        *a_odd = b_odd;
+                        if i % 2 == 1 {
+                            if self.flip_even {
+                                *a_even *= -T::one();
+                            } else {
+                                *a_odd *= -T::one();
+                            }
+                        }
+                    });
+                self.ifft.process(&mut buf_even);
+                self.ifft.process(&mut buf_odd);
+                let y_even = self.filter_even.filter(&buf_even);
+                let y_odd = self.filter_odd.filter(&buf_odd);
+                result.extend(
+                    y_even
+                        .iter()
+                        .zip(self.buffer.iter().chain(y_odd.iter()))
+                        .map(|(&a, &b)| a + b),
+                );
+                self.buffer.copy_from_slice(&y_odd[nch / 2..]);
+            });
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cspfb::tests::white_noise, windowed_fir::pfb_coeff};
+
+    #[test]
+    fn synthesizer_reconstruction_test() {
+        let nch = 32;
+        for (tap_per_ch, tap_syn) in [(16, 16), (15, 16)] {
+            let coeff = pfb_coeff::<f64>(nch, tap_per_ch, 1.55 * 1.1);
+            let coeff_syn = pfb_coeff::<f64>(nch, tap_syn, 1.0 + 1.0 / tap_syn as f64);
+            let mut ana = Analyzer::<Complex<f64>, f64>::new(nch, coeff.as_slice().unwrap());
+            let mut syn = Synthesizer::<f64>::new(
+                nch,
+                coeff.as_slice().unwrap(),
+                coeff_syn.as_slice().unwrap(),
+            );
+
+            let signal = white_noise(nch * 2048, 3);
+            let mut output = Vec::new();
+            for x in signal.chunks(nch * 10) {
+                output.extend(syn.synthesize(ana.analyze(x).view()));
+            }
+            let d = syn.group_delay();
+            let (e2, s2) = output[d..]
+                .iter()
+                .zip(&signal)
+                .fold((0.0, 0.0), |(e, s), (a, b)| {
+                    (e + (a - b).norm_sqr(), s + b.norm_sqr())
+                });
+            let err = (e2 / s2).sqrt();
+            assert!(syn.reconstruction_error() < 1e-3);
+            assert!((err - syn.reconstruction_error()).abs() < 1e-4);
+        }
     }
 }
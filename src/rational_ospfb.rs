@@ -0,0 +1,422 @@
+//! oversampling poly phase filter bank with arbitrary rational oversampling factor
+
+use crate::{
+    cfg::PfbCfg,
+    channelizer::{ChannelFrequency, Channelizer, OutputLayout},
+    timing::{GroupDelay, Timing},
+    utils::FftPlan,
+    windowed_fir::pfb_coeff,
+};
+use ndarray::{parallel::prelude::*, Array2, Axis, ScalarOperand};
+use num::{
+    complex::Complex,
+    traits::{Float, FloatConst, NumAssign},
+};
+
+use serde::{Deserialize, Serialize};
+
+use rustfft::FftNum;
+use std::{iter::Sum, ops::Mul};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Analyzer<R, T>
+where
+    R: std::fmt::Debug,
+    T: std::fmt::Debug + FftNum,
+{
+    /// reversed prototype low pass filter coefficients
+    coeff_rev: Vec<T>,
+
+    /// number of channels
+    nch: usize,
+
+    /// oversampling factor (P, Q)
+    oversampling: (usize, usize),
+
+    /// input samples that have not been completely consumed, the first `coeff_rev.len()-hop` of which are the filter state
+    state: Vec<R>,
+
+    /// absolute index of `state[0]` modulo `nch`, by which the folded input is circularly rotated before the fft
+    rot: usize,
+
+    /// number of frames that have been produced, see [`Self::timing`]
+    #[serde(default)]
+    nframes: usize,
+
+    /// fft plan, planned again with `nch` if missing from the serialized form
+    #[serde(default = "FftPlan::default")]
+    fft: FftPlan<T>,
+}
+
+impl<R, T> Analyzer<R, T>
+where
+    T: Copy
+        + Float
+        + FloatConst
+        + ScalarOperand
+        + NumAssign
+        + std::fmt::Debug
+        + Sync
+        + Send
+        + FftNum,
+    R: Copy
+        + Mul<T, Output = R>
+        + Default
+        + ScalarOperand
+        + NumAssign
+        + std::fmt::Debug
+        + Sum
+        + Sync
+        + Send,
+    Complex<T>: Copy + std::convert::From<R> + Sum + Default + ScalarOperand,
+{
+    /// constructor
+    /// * `nch` - number of channels including both pos and neg ones
+    /// * `coeff` - prototype low pass filter, the length of which should be `nch` times of the tap per channel
+    /// * `oversampling` - oversampling factor (P, Q) with `P >= Q > 0`, so that the hop length `nch*Q/P` should be a positive integer
+    /// ```
+    /// extern crate rsdsp;
+    /// use num::complex::Complex;
+    /// use rsdsp::{
+    ///     windowed_fir
+    ///     , rational_ospfb::Analyzer
+    /// };
+    ///
+    /// let nch=32;
+    /// let tap_per_ch=16;
+    /// let k=1.1;
+    /// let coeff=windowed_fir::pfb_coeff::<f64>(nch, tap_per_ch, k);
+    /// let mut pfb=Analyzer::<Complex<f64>, f64>::new(nch, coeff.as_slice().unwrap(), (4, 3));
+    /// let input_signal=vec![Complex::<f64>::new(1.0, 0.0); 240];
+    /// let channelized_signal=pfb.analyze(&input_signal);
+    /// assert_eq!(channelized_signal.shape(), &[nch, 10]);
+    /// ```
+    pub fn new(nch: usize, coeff: &[T], oversampling: (usize, usize)) -> Self {
+        let tap = coeff.len() / nch;
+        assert!(nch * tap == coeff.len());
+        let (p, q) = oversampling;
+        assert!(
+            q > 0 && p >= q,
+            "oversampling factor should satisfy P >= Q > 0, but is ({p}, {q})"
+        );
+        let hop = nch * q / p;
+        assert!(hop > 0 && hop * p == nch * q);
+
+        let coeff_rev: Vec<_> = coeff.iter().rev().cloned().collect();
+        let nstate = coeff.len() - hop;
+        Self {
+            coeff_rev,
+            nch,
+            oversampling,
+            state: vec![R::default(); nstate],
+            rot: (nch - nstate % nch) % nch,
+            nframes: 0,
+            fft: FftPlan::forward(nch),
+        }
+    }
+
+    /// return the number of channels
+    pub fn nch(&self) -> usize {
+        self.nch
+    }
+
+    /// oversampling factor (P, Q)
+    pub fn oversampling(&self) -> (usize, usize) {
+        self.oversampling
+    }
+
+    /// number of input samples between successive output frames
+    pub fn hop(&self) -> usize {
+        self.nch * self.oversampling.1 / self.oversampling.0
+    }
+
+    /// frequencies of the output channels in the unit of the input sampling rate, one for each row of the output of [`Self::analyze`],
+    /// with the center frequencies in `[-0.5, 0.5)`
+    pub fn normalized_channel_frequencies(&self) -> Vec<ChannelFrequency<T>> {
+        let (p, q) = self.oversampling;
+        ChannelFrequency::fft_order(self.nch, T::from(p).unwrap() / T::from(q).unwrap())
+    }
+
+    /// physical frequencies of the output channels, see [`Self::normalized_channel_frequencies`]
+    /// * `sample_rate` - sampling rate of the input signal
+    /// * `center_freq` - physical frequency of the DC of the input signal
+    pub fn channel_frequencies(&self, sample_rate: T, center_freq: T) -> Vec<ChannelFrequency<T>> {
+        self.normalized_channel_frequencies()
+            .into_iter()
+            .map(|f| f.to_physical(sample_rate, center_freq))
+            .collect()
+    }
+
+    pub fn predict_output_length(&self, input_len: usize) -> usize {
+        (self.state.len() + input_len + self.hop() - self.coeff_rev.len()) / self.hop()
+    }
+
+    /// timing of the output frames, i.e., frame `j` is at `(j+1)*hop-1-(coeff.len()-1)/2` input samples
+    pub fn timing(&self) -> Timing {
+        Timing::frames(self.nframes, self.hop(), self.coeff_rev.len())
+    }
+
+    /// group delay, i.e., `(coeff.len()-1)/2` input samples
+    pub fn group_delay(&self) -> GroupDelay {
+        GroupDelay::fir(self.coeff_rev.len(), self.hop() as f64)
+    }
+
+    /// fold the windowed input of the `i`-th frame into `nch` points, and circularly rotate it according to the absolute time
+    fn fold(&self, i: usize, output: &mut [Complex<T>]) {
+        let nch = self.nch;
+        let start = i * self.hop();
+        output.iter_mut().for_each(|x| *x = Complex::<T>::default());
+        self.state[start..start + self.coeff_rev.len()]
+            .iter()
+            .zip(self.coeff_rev.iter())
+            .enumerate()
+            .for_each(|(j, (&x, &c))| {
+                output[(self.rot + start + j) % nch] += Complex::<T>::from(x * c);
+            });
+    }
+
+    /// drop the input samples that are no longer needed, after `batch` frames have been produced
+    fn consume(&mut self, batch: usize) {
+        let n = batch * self.hop();
+        self.state.drain(..n);
+        self.rot = (self.rot + n) % self.nch;
+        self.nframes += batch;
+    }
+
+    /// Channelize input signal
+    /// * `input_signal` - a 1-d slice containing time domain input signal
+    /// * return value - channelized data, with `batch` rows and `nch` columns
+    pub fn analyze_raw(&mut self, input_signal: &[R]) -> Array2<Complex<T>> {
+        let nch = self.nch;
+        let batch = self.predict_output_length(input_signal.len());
+        self.state.extend_from_slice(input_signal);
+
+        let mut result = Array2::<Complex<T>>::default((batch, nch));
+
+        result
+            .axis_iter_mut(Axis(0))
+            .enumerate()
+            .for_each(|(i, mut x)| self.fold(i, x.as_slice_mut().unwrap()));
+        self.fft.ensure_len(nch);
+        self.fft.process(result.as_slice_mut().unwrap());
+        self.consume(batch);
+        result
+    }
+
+    /// Channelize input signal
+    /// * `input_signal` - a 1-d slice containing time domain input signal
+    /// * return value - channelized data, with `nch` rows
+    pub fn analyze(&mut self, input_signal: &[R]) -> Array2<Complex<T>> {
+        self.analyze_raw(input_signal)
+            .t()
+            .as_standard_layout()
+            .to_owned()
+    }
+
+    /// Parallel version of [`Self::analyze_raw`]
+    pub fn analyze_raw_par(&mut self, input_signal: &[R]) -> Array2<Complex<T>> {
+        let nch = self.nch;
+        let batch = self.predict_output_length(input_signal.len());
+        self.state.extend_from_slice(input_signal);
+
+        let mut result = Array2::<Complex<T>>::default((batch, nch));
+
+        result
+            .axis_iter_mut(Axis(0))
+            .into_par_iter()
+            .enumerate()
+            .for_each(|(i, mut x)| self.fold(i, x.as_slice_mut().unwrap()));
+        self.fft.ensure_len(nch);
+        self.fft.process_par(result.as_slice_mut().unwrap());
+        self.consume(batch);
+        result
+    }
+
+    /// Parallel version of [`Self::analyze`]
+    pub fn analyze_par(&mut self, input_signal: &[R]) -> Array2<Complex<T>> {
+        self.analyze_raw_par(input_signal)
+            .t()
+            .as_standard_layout()
+            .to_owned()
+    }
+}
+
+impl<R, T> Channelizer for Analyzer<R, T>
+where
+    T: Copy
+        + Float
+        + FloatConst
+        + ScalarOperand
+        + NumAssign
+        + std::fmt::Debug
+        + Sync
+        + Send
+        + FftNum,
+    R: Copy
+        + Mul<T, Output = R>
+        + Default
+        + ScalarOperand
+        + NumAssign
+        + std::fmt::Debug
+        + Sum
+        + Sync
+        + Send,
+    Complex<T>: Copy + std::convert::From<R> + Sum + Default + ScalarOperand,
+{
+    type Input = [R];
+    type Output = Complex<T>;
+
+    fn nch(&self) -> usize {
+        self.nch
+    }
+
+    fn output_layout(&self) -> OutputLayout {
+        OutputLayout::FftOrder
+    }
+
+    fn predict_output_length(&self, input_len: usize) -> usize {
+        Analyzer::predict_output_length(self, input_len)
+    }
+
+    fn timing(&self) -> Timing {
+        Analyzer::timing(self)
+    }
+
+    fn group_delay(&self) -> GroupDelay {
+        Analyzer::group_delay(self)
+    }
+
+    fn analyze(&mut self, input: &[R]) -> Array2<Complex<T>> {
+        Analyzer::analyze(self, input)
+    }
+
+    fn analyze_par(&mut self, input: &[R]) -> Array2<Complex<T>> {
+        Analyzer::analyze_par(self, input)
+    }
+}
+
+/// construct an [`Analyzer`] from [`crate::cfg::PfbCfg`], the oversampling factor of which should be given
+pub fn cfg2pfb<R, T>(cfg: &PfbCfg) -> Analyzer<R, T>
+where
+    T: Copy
+        + Float
+        + FloatConst
+        + ScalarOperand
+        + NumAssign
+        + std::fmt::Debug
+        + Sum
+        + Sync
+        + Send
+        + FftNum,
+    R: Copy
+        + Mul<T, Output = R>
+        + Default
+        + ScalarOperand
+        + NumAssign
+        + std::fmt::Debug
+        + Sum
+        + Sync
+        + Send,
+    Complex<T>: Copy + std::convert::From<R> + Sum + Default + ScalarOperand,
+{
+    let coeff = pfb_coeff::<T>(cfg.nch, cfg.tap_per_ch, T::from(cfg.k).unwrap());
+    let oversampling = cfg
+        .oversampling
+        .expect("the oversampling factor is required by a rational ospfb");
+    assert!(
+        oversampling.1 > 0,
+        "Q of the oversampling factor (P, Q) should be positive"
+    );
+    Analyzer::new(cfg.nch, coeff.as_slice().unwrap(), oversampling)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cspfb, cspfb::tests::white_noise, ospfb2};
+
+    #[test]
+    fn consistency_test() {
+        let nch = 32;
+        let tap_per_ch = 8;
+        let coeff = pfb_coeff::<f64>(nch, tap_per_ch, 1.1);
+        let coeff = coeff.as_slice().unwrap();
+        let signal = white_noise(nch * 64, 4);
+
+        // critical sampling is the same as cspfb
+        let mut pfb = Analyzer::<Complex<f64>, f64>::new(nch, coeff, (1, 1));
+        let mut pfb0 = cspfb::Analyzer::<Complex<f64>, f64>::new(nch, coeff);
+        let y = pfb.analyze(&signal);
+        let y0 = pfb0.analyze(&signal);
+        assert_eq!(y.shape(), y0.shape());
+        assert!(y.iter().zip(y0.iter()).all(|(a, b)| (a - b).norm() < 1e-12));
+
+        // 2x oversampling is the same as ospfb2, apart from the sign of odd channels,
+        // which is referenced to the absolute time here
+        let mut pfb = Analyzer::<Complex<f64>, f64>::new(nch, coeff, (2, 1));
+        let mut pfb0 = ospfb2::Analyzer::<Complex<f64>, f64>::new(nch, coeff);
+        let y = pfb.analyze(&signal);
+        let y0 = pfb0.analyze(&signal);
+        assert_eq!(y.shape(), y0.shape());
+        y.axis_iter(Axis(0))
+            .zip(y0.axis_iter(Axis(0)))
+            .enumerate()
+            .for_each(|(k, (a, b))| {
+                let s = if k % 2 == 0 { 1.0 } else { -1.0 };
+                assert!(a
+                    .iter()
+                    .zip(b.iter())
+                    .all(|(a, b)| (a - b * s).norm() < 1e-12));
+            });
+    }
+
+    #[test]
+    fn streaming_test() {
+        let cfg = PfbCfg {
+            nch: 32,
+            tap_per_ch: 8,
+            k: 1.1,
+            oversampling: Some((4, 3)),
+        };
+        let mut pfb1 = cfg2pfb::<Complex<f64>, f64>(&cfg);
+        let mut pfb2 = pfb1.clone();
+        assert_eq!(pfb1.hop(), 24);
+        let signal = white_noise(cfg.nch * 64, 5);
+        let y1 = pfb1.analyze(&signal);
+        let y2 = signal
+            .chunks(37)
+            .map(|x| pfb2.analyze_par(x))
+            .reduce(|a, b| ndarray::concatenate![Axis(1), a, b])
+            .unwrap();
+        assert_eq!(y1.shape(), &[cfg.nch, signal.len() / pfb1.hop()]);
+        assert_eq!(y1.shape(), y2.shape());
+        assert!(y1
+            .iter()
+            .zip(y2.iter())
+            .all(|(a, b)| (a - b).norm() < 1e-12));
+    }
+
+    #[test]
+    #[should_panic(expected = "oversampling factor is required")]
+    fn cfg_without_oversampling_test() {
+        // e.g., a cfg written for the 2x ospfb, which should not be read as critically sampled
+        let cfg: PfbCfg = serde_yaml::from_str("nch: 32\ntap_per_ch: 8\nk: 1.1\n").unwrap();
+        assert_eq!(cfg.oversampling, None);
+        cfg2pfb::<Complex<f64>, f64>(&cfg);
+    }
+
+    #[test]
+    #[should_panic(expected = "Q of the oversampling factor (P, Q) should be positive")]
+    fn cfg_zero_q_test() {
+        let cfg: PfbCfg =
+            serde_yaml::from_str("nch: 32\ntap_per_ch: 8\nk: 1.1\noversampling: [4, 0]\n").unwrap();
+        cfg2pfb::<Complex<f64>, f64>(&cfg);
+    }
+
+    #[test]
+    #[should_panic(expected = "oversampling factor should satisfy P >= Q > 0")]
+    fn zero_q_test() {
+        let coeff = pfb_coeff::<f64>(32, 8, 1.1);
+        Analyzer::<Complex<f64>, f64>::new(32, coeff.as_slice().unwrap(), (4, 0));
+    }
+}
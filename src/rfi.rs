@@ -0,0 +1,291 @@
+//! RFI detection on channelized data, the flags of which can be passed to
+//! [`crate::spectrometer::Spectrometer`] and [`crate::correlator::Correlator`] to exclude the flagged samples
+
+use ndarray::{parallel::prelude::*, s, Array2, ArrayView2, Axis};
+use num::{
+    complex::Complex,
+    traits::{float::TotalOrder, Float},
+};
+
+/// Generalized spectral kurtosis (SK) detector, see Nita & Gary (2010), MNRAS 406, L60.
+///
+/// The frames of each channel are divided into blocks of `block_len` frames, the SK estimator of each block is
+/// `(M*d+1)/(M-1)*(M*S2/S1^2-1)`, where `S1` and `S2` are the sums of the power and the squared power,
+/// `M` is the number of frames in the block, and `d` is the shape factor of the gamma distribution of the power.
+/// The estimator is 1 for Gaussian noise, and a block is flagged if it is out of `[lower, upper]`.
+#[derive(Debug, Clone, Copy)]
+pub struct SkDetector<T> {
+    /// number of frames in each block, i.e., `M`
+    pub block_len: usize,
+    /// shape factor, which is 1 for the power of complex voltages
+    pub d: T,
+    /// lower threshold
+    pub lower: T,
+    /// upper threshold
+    pub upper: T,
+}
+
+impl<T> SkDetector<T>
+where
+    T: Float + std::fmt::Debug + Send + Sync,
+{
+    /// construct a detector for the power of complex voltages, i.e., `d=1`,
+    /// with symmetric thresholds of `nsigma` times of the standard deviation of the estimator for Gaussian noise
+    /// * `block_len` - number of frames in each block
+    /// * `nsigma` - thresholds in the unit of the standard deviation
+    /// ```
+    /// extern crate rsdsp;
+    /// use rsdsp::rfi::SkDetector;
+    ///
+    /// let sk=SkDetector::<f64>::with_sigma(256, 3.0);
+    /// assert!(sk.lower<1.0 && sk.upper>1.0);
+    /// ```
+    pub fn with_sigma(block_len: usize, nsigma: T) -> Self {
+        let d = T::one();
+        let sigma = Self::variance(block_len, d).sqrt();
+        Self {
+            block_len,
+            d,
+            lower: T::one() - nsigma * sigma,
+            upper: T::one() + nsigma * sigma,
+        }
+    }
+
+    /// variance of the estimator for Gaussian noise, i.e., `2*d*(d+1)*M^2/((M-1)*(M*d+2)*(M*d+3))`
+    pub fn variance(block_len: usize, d: T) -> T {
+        assert!(block_len > 1);
+        let m = T::from(block_len).unwrap();
+        let two = T::one() + T::one();
+        let three = two + T::one();
+        two * d * (d + T::one()) * m * m / ((m - T::one()) * (m * d + two) * (m * d + three))
+    }
+
+    /// SK estimator of the power of a block, which should contain at least 2 frames
+    pub fn estimate<'a, I>(&self, power: I) -> T
+    where
+        I: IntoIterator<Item = &'a T>,
+        T: 'a,
+    {
+        let (m, s1, s2) = power
+            .into_iter()
+            .fold((T::zero(), T::zero(), T::zero()), |(m, s1, s2), &p| {
+                (m + T::one(), s1 + p, s2 + p * p)
+            });
+        assert!(m > T::one(), "a block should contain at least 2 frames");
+        (m * self.d + T::one()) / (m - T::one()) * (m * s2 / (s1 * s1) - T::one())
+    }
+
+    /// SK estimators of all blocks, the channels are processed in parallel
+    /// * `x` - channelized data of the shape `(nch, ntime)`, e.g., the output of `analyze`
+    /// * return value - estimators of the shape `(nch, nblocks)`, the trailing frames that are not enough for a block are ignored
+    pub fn estimators(&self, x: ArrayView2<Complex<T>>) -> Array2<T> {
+        assert!(self.block_len > 1, "block_len should be at least 2");
+        let nblocks = x.ncols() / self.block_len;
+        let mut result = Array2::from_elem((x.nrows(), nblocks), T::zero());
+        result
+            .outer_iter_mut()
+            .into_par_iter()
+            .zip(x.outer_iter().into_par_iter())
+            .for_each(|(mut sk, x)| {
+                sk.iter_mut().enumerate().for_each(|(b, sk)| {
+                    let power: Vec<_> = x
+                        .slice(s![b * self.block_len..(b + 1) * self.block_len])
+                        .iter()
+                        .map(|x| x.norm_sqr())
+                        .collect();
+                    *sk = self.estimate(&power);
+                })
+            });
+        result
+    }
+
+    /// flag the blocks with the SK estimators out of the thresholds
+    /// * `x` - channelized data of the shape `(nch, ntime)`
+    /// * return value - flags of the same shape as `x`, `true` for the samples contaminated by RFI,
+    ///   the trailing frames that are not enough for a block are not flagged
+    pub fn flag(&self, x: ArrayView2<Complex<T>>) -> Array2<bool> {
+        let sk = self.estimators(x);
+        Array2::from_shape_fn(x.dim(), |(ch, t)| {
+            sk.get((ch, t / self.block_len))
+                .is_some_and(|&sk| !(sk >= self.lower && sk <= self.upper))
+        })
+    }
+}
+
+/// median of the values, which should not be empty and are reordered, NaNs being sorted to the ends
+fn median<T>(v: &mut [T]) -> T
+where
+    T: Float + TotalOrder,
+{
+    let n = v.len();
+    assert!(n > 0);
+    let two = T::one() + T::one();
+    v.sort_unstable_by(|a, b| a.total_cmp(b));
+    if n % 2 == 1 {
+        v[n / 2]
+    } else {
+        (v[n / 2 - 1] + v[n / 2]) / two
+    }
+}
+
+/// flag the outliers of the values with the median absolute deviation (MAD), NaNs being flagged
+fn flag_mad<T>(power: &[T], threshold: T, flags: &mut [bool])
+where
+    T: Float + TotalOrder,
+{
+    if power.is_empty() {
+        return;
+    }
+    let mut v = power.to_vec();
+    let med = median(&mut v);
+    v.iter_mut()
+        .zip(power)
+        .for_each(|(v, &p)| *v = (p - med).abs());
+    // scale the MAD to the standard deviation of Gaussian distribution
+    let sigma = median(&mut v) * T::from(1.4826).unwrap();
+    flags
+        .iter_mut()
+        .zip(power)
+        .for_each(|(f, &p)| *f = p.is_nan() || (p - med).abs() > threshold * sigma);
+}
+
+/// Axis along which the median absolute deviation is computed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MadAxis {
+    /// over the frames of each channel, which detects bursty RFI
+    Time,
+    /// over the channels of each frame, which detects narrow band RFI
+    Frequency,
+}
+
+/// Median absolute deviation (MAD) detector, which flags the samples with the power deviating from the median
+/// by more than `threshold` times of the standard deviation estimated from the MAD
+#[derive(Debug, Clone, Copy)]
+pub struct MadDetector<T> {
+    /// axis along which the median and the MAD are computed
+    pub axis: MadAxis,
+    /// threshold in the unit of the standard deviation
+    pub threshold: T,
+}
+
+impl<T> MadDetector<T>
+where
+    T: Float + TotalOrder + std::fmt::Debug + Send + Sync,
+{
+    /// flag the outliers, the rows (or columns) are processed in parallel
+    /// * `x` - channelized data of the shape `(nch, ntime)`
+    /// * return value - flags of the same shape as `x`, `true` for the samples contaminated by RFI
+    pub fn flag(&self, x: ArrayView2<Complex<T>>) -> Array2<bool> {
+        let axis = match self.axis {
+            MadAxis::Time => Axis(0),
+            MadAxis::Frequency => Axis(1),
+        };
+        let mut result = Array2::from_elem(x.dim(), false);
+        result
+            .axis_iter_mut(axis)
+            .into_par_iter()
+            .zip(x.axis_iter(axis).into_par_iter())
+            .for_each(|(mut f, x)| {
+                let power: Vec<_> = x.iter().map(|x| x.norm_sqr()).collect();
+                let mut flags = vec![false; power.len()];
+                flag_mad(&power, self.threshold, &mut flags);
+                f.iter_mut().zip(flags).for_each(|(f, f1)| *f = f1);
+            });
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cspfb::tests::white_noise;
+
+    /// complex gaussian noise from the sum of uniform noise
+    fn gaussian_noise(n: usize, seed: u64) -> Vec<Complex<f64>> {
+        let u = white_noise(n * 12, seed);
+        u.chunks(12).map(|x| x.iter().sum()).collect()
+    }
+
+    #[test]
+    fn sk_test() {
+        let nch = 16;
+        let block_len = 512;
+        let ntime = block_len * 8;
+        let mut x = Array2::from_shape_vec((nch, ntime), gaussian_noise(nch * ntime, 11)).unwrap();
+        // a pulsed RFI in channel 3, block 2, and a CW in channel 9
+        x.slice_mut(s![3, 2 * block_len..2 * block_len + 20])
+            .mapv_inplace(|x| x + 30.0);
+        x.row_mut(9)
+            .iter_mut()
+            .enumerate()
+            .for_each(|(t, x)| *x = *x * 0.01 + Complex::from_polar(10.0, t as f64 * 0.1));
+        let sk = SkDetector::<f64>::with_sigma(block_len, 5.0);
+        let est = sk.estimators(x.view());
+        assert_eq!(est.dim(), (nch, 8));
+        assert!(est[[3, 2]] > sk.upper);
+        assert!(est.row(9).iter().all(|&e| e < sk.lower));
+        let flags = sk.flag(x.view());
+        (0..nch).for_each(|ch| {
+            (0..ntime).for_each(|t| {
+                let expected = ch == 9 || (ch == 3 && t / block_len == 2);
+                assert_eq!(flags[[ch, t]], expected, "{ch} {t}");
+            })
+        });
+    }
+
+    #[test]
+    fn mad_test() {
+        let nch = 32;
+        let ntime = 200;
+        let mut x = Array2::from_shape_vec((nch, ntime), gaussian_noise(nch * ntime, 12)).unwrap();
+        x[[5, 17]] = Complex::new(100.0, 0.0);
+        x.row_mut(20).mapv_inplace(|x| x * 50.0);
+        let flags_t = MadDetector {
+            axis: MadAxis::Time,
+            threshold: 20.0,
+        }
+        .flag(x.view());
+        // the channel with the constant high power is not an outlier in time
+        assert_eq!(flags_t.iter().filter(|&&f| f).count(), 1);
+        assert!(flags_t[[5, 17]]);
+        let flags_f = MadDetector {
+            axis: MadAxis::Frequency,
+            threshold: 20.0,
+        }
+        .flag(x.view());
+        assert!(flags_f[[5, 17]]);
+        assert!(flags_f.row(20).iter().filter(|&&f| f).count() > ntime / 2);
+    }
+
+    #[test]
+    fn mad_edge_case_test() {
+        let detector = |axis| MadDetector {
+            axis,
+            threshold: 20.0,
+        };
+        // empty chunks
+        for dim in [(8, 0), (0, 8)] {
+            let x = Array2::<Complex<f64>>::zeros(dim);
+            assert_eq!(detector(MadAxis::Time).flag(x.view()).dim(), dim);
+            assert_eq!(detector(MadAxis::Frequency).flag(x.view()).dim(), dim);
+        }
+        // NaN samples are flagged instead of panicking
+        let mut x = Array2::from_shape_vec((4, 50), gaussian_noise(200, 13)).unwrap();
+        x[[1, 7]] = Complex::new(f64::NAN, 0.0);
+        let flags = detector(MadAxis::Time).flag(x.view());
+        assert!(flags[[1, 7]]);
+        assert_eq!(flags.iter().filter(|&&f| f).count(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "block_len should be at least 2")]
+    fn sk_block_len_test() {
+        let sk = SkDetector {
+            block_len: 1,
+            d: 1.0,
+            lower: 0.5,
+            upper: 1.5,
+        };
+        sk.flag(Array2::<Complex<f64>>::zeros((4, 8)).view());
+    }
+}
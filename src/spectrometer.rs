@@ -0,0 +1,347 @@
+//! Integrating spectrometer working on channelized data
+
+use ndarray::{parallel::prelude::*, s, Array2, ArrayView2, Axis};
+use num::{complex::Complex, traits::Float};
+
+/// Products accumulated by a [`Spectrometer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpectrumKind {
+    /// `|X|^2` of a single stream
+    Power,
+    /// Stokes parameters of dual linear polarizations `X` and `Y`, in the order of
+    /// `I=|X|^2+|Y|^2`, `Q=|X|^2-|Y|^2`, `U=2*Re(X*conj(Y))` and `V=2*Im(conj(X)*Y)`
+    Stokes,
+}
+
+impl SpectrumKind {
+    /// number of products, i.e., rows of [`IntegratedSpectrum::data`]
+    pub fn nprod(self) -> usize {
+        match self {
+            SpectrumKind::Power => 1,
+            SpectrumKind::Stokes => 4,
+        }
+    }
+}
+
+/// One integrated spectrum
+#[derive(Debug, Clone)]
+pub struct IntegratedSpectrum<T> {
+    /// index of the first frame of the integration, counted from the first frame that has been fed
+    pub first_frame: usize,
+    /// time of the center of the integration, in seconds
+    pub time: T,
+    /// accumulated products of the shape `(nprod, nch)`, see [`SpectrumKind`]
+    pub data: Array2<T>,
+    /// number of frames accumulated in each channel, which is less than `int_len` if some frames are flagged
+    pub nvalid: Vec<usize>,
+}
+
+/// Integrating spectrometer, which accumulates the power of the output of an analyzer over `int_len` frames
+#[derive(Debug, Clone)]
+pub struct Spectrometer<T> {
+    kind: SpectrumKind,
+    /// number of frames integrated in each spectrum
+    int_len: usize,
+    /// number of frames per second, i.e., the sampling rate of the channelized data
+    frame_rate: T,
+    /// products being integrated, of the shape `(nprod, nch)`
+    acc: Array2<T>,
+    /// number of frames accumulated in each channel of `acc`
+    count: Vec<usize>,
+    /// number of frames that have been integrated in `acc`
+    nacc: usize,
+    /// number of frames that have been fed
+    nframes: usize,
+}
+
+impl<T> Spectrometer<T>
+where
+    T: Float + std::fmt::Debug + Send + Sync,
+{
+    /// constructor
+    /// * `kind` - products to be accumulated
+    /// * `nch` - number of channels
+    /// * `int_len` - number of frames integrated in each spectrum
+    /// * `frame_rate` - number of frames per second, e.g., [`crate::channelizer::ChannelFrequency::sample_rate`]
+    /// ```
+    /// extern crate rsdsp;
+    /// use num::complex::Complex;
+    /// use rsdsp::{
+    ///     windowed_fir
+    ///     , cspfb::Analyzer
+    ///     , spectrometer::{Spectrometer, SpectrumKind}
+    /// };
+    ///
+    /// let nch=16;
+    /// let coeff=windowed_fir::pfb_coeff::<f64>(nch, 8, 1.1);
+    /// let mut pfb=Analyzer::<Complex<f64>, f64>::new(nch, coeff.as_slice().unwrap());
+    /// let mut spec=Spectrometer::<f64>::new(SpectrumKind::Power, nch, 4, 1.0);
+    /// let x=vec![Complex::<f64>::new(1.0, 0.0); nch*10];
+    /// let spectra=spec.integrate_power(pfb.analyze(&x).view());
+    /// // the remaining 2 frames are integrated with the next input
+    /// assert_eq!(spectra.len(), 2);
+    /// assert_eq!(spectra[1].first_frame, 4);
+    /// assert_eq!(spectra[1].time, 6.0);
+    /// ```
+    pub fn new(kind: SpectrumKind, nch: usize, int_len: usize, frame_rate: T) -> Self {
+        assert!(int_len > 0);
+        Self {
+            kind,
+            int_len,
+            frame_rate,
+            acc: Array2::from_elem((kind.nprod(), nch), T::zero()),
+            count: vec![0; nch],
+            nacc: 0,
+            nframes: 0,
+        }
+    }
+
+    /// products accumulated
+    pub fn kind(&self) -> SpectrumKind {
+        self.kind
+    }
+
+    /// number of channels
+    pub fn nch(&self) -> usize {
+        self.acc.ncols()
+    }
+
+    /// number of frames integrated in each spectrum
+    pub fn int_len(&self) -> usize {
+        self.int_len
+    }
+
+    /// number of spectra that an input with `ntime` frames will produce
+    pub fn predict_output_length(&self, ntime: usize) -> usize {
+        (self.nacc + ntime) / self.int_len
+    }
+
+    /// accumulate the frames of all streams, the channels are processed in parallel
+    /// * `streams` - one `(nch, n)` array for each polarization
+    /// * `flags` - `(nch, n)` flags of the samples to be excluded, which apply to all streams
+    fn accumulate(&mut self, streams: &[ArrayView2<Complex<T>>], flags: Option<ArrayView2<bool>>) {
+        let two = T::one() + T::one();
+        let kind = self.kind;
+        self.acc
+            .axis_iter_mut(Axis(1))
+            .into_par_iter()
+            .zip(self.count.par_iter_mut())
+            .enumerate()
+            .for_each(|(ch, (mut acc, count))| {
+                let valid = |t: usize| flags.is_none_or(|f| !f[[ch, t]]);
+                match kind {
+                    SpectrumKind::Power => {
+                        streams[0]
+                            .row(ch)
+                            .iter()
+                            .enumerate()
+                            .filter(|&(t, _)| valid(t))
+                            .for_each(|(_, x)| {
+                                acc[0] = acc[0] + x.norm_sqr();
+                                *count += 1;
+                            });
+                    }
+                    SpectrumKind::Stokes => {
+                        streams[0]
+                            .row(ch)
+                            .iter()
+                            .zip(streams[1].row(ch))
+                            .enumerate()
+                            .filter(|&(t, _)| valid(t))
+                            .for_each(|(_, (&x, &y))| {
+                                let xx = x.norm_sqr();
+                                let yy = y.norm_sqr();
+                                let xy = x * y.conj();
+                                acc[0] = acc[0] + xx + yy;
+                                acc[1] = acc[1] + xx - yy;
+                                acc[2] = acc[2] + two * xy.re;
+                                acc[3] = acc[3] - two * xy.im;
+                                *count += 1;
+                            });
+                    }
+                }
+            });
+    }
+
+    /// integrate the frames, the frames that are not enough for an integration are kept for the next call
+    fn integrate(
+        &mut self,
+        streams: &[ArrayView2<Complex<T>>],
+        flags: Option<ArrayView2<bool>>,
+    ) -> Vec<IntegratedSpectrum<T>> {
+        let ntime = streams[0].ncols();
+        streams.iter().for_each(|x| {
+            assert_eq!(x.nrows(), self.nch());
+            assert_eq!(x.ncols(), ntime);
+        });
+        if let Some(f) = flags {
+            assert_eq!(f.dim(), streams[0].dim());
+        }
+        let mut result = Vec::with_capacity(self.predict_output_length(ntime));
+        let mut start = 0;
+        while start < ntime {
+            let n = (self.int_len - self.nacc).min(ntime - start);
+            let chunk: Vec<_> = streams
+                .iter()
+                .map(|x| x.slice(s![.., start..start + n]))
+                .collect();
+            self.accumulate(
+                &chunk,
+                flags.map(|f| f.slice_move(s![.., start..start + n])),
+            );
+            self.nacc += n;
+            self.nframes += n;
+            start += n;
+            if self.nacc == self.int_len {
+                let first_frame = self.nframes - self.int_len;
+                let two = T::one() + T::one();
+                let time = (T::from(first_frame).unwrap() + T::from(self.int_len).unwrap() / two)
+                    / self.frame_rate;
+                result.push(IntegratedSpectrum {
+                    first_frame,
+                    time,
+                    data: self.acc.clone(),
+                    nvalid: self.count.clone(),
+                });
+                self.acc.fill(T::zero());
+                self.count.fill(0);
+                self.nacc = 0;
+            }
+        }
+        result
+    }
+
+    /// integrate `|X|^2` of one stream, which should be constructed with [`SpectrumKind::Power`]
+    /// * `x` - channelized data of the shape `(nch, ntime)`, e.g., the output of `analyze`
+    pub fn integrate_power(&mut self, x: ArrayView2<Complex<T>>) -> Vec<IntegratedSpectrum<T>> {
+        assert_eq!(self.kind, SpectrumKind::Power);
+        self.integrate(&[x], None)
+    }
+
+    /// the same as [`Self::integrate_power`], but the samples flagged by `flags` are excluded,
+    /// see [`crate::rfi`]
+    /// * `flags` - flags of the same shape as `x`, `true` for the samples to be excluded
+    pub fn integrate_power_masked(
+        &mut self,
+        x: ArrayView2<Complex<T>>,
+        flags: ArrayView2<bool>,
+    ) -> Vec<IntegratedSpectrum<T>> {
+        assert_eq!(self.kind, SpectrumKind::Power);
+        self.integrate(&[x], Some(flags))
+    }
+
+    /// integrate the Stokes parameters of two streams, which should be constructed with [`SpectrumKind::Stokes`]
+    /// * `x` - channelized data of the `X` polarization of the shape `(nch, ntime)`
+    /// * `y` - channelized data of the `Y` polarization of the same shape
+    pub fn integrate_stokes(
+        &mut self,
+        x: ArrayView2<Complex<T>>,
+        y: ArrayView2<Complex<T>>,
+    ) -> Vec<IntegratedSpectrum<T>> {
+        assert_eq!(self.kind, SpectrumKind::Stokes);
+        self.integrate(&[x.view(), y.view()], None)
+    }
+
+    /// the same as [`Self::integrate_stokes`], but the samples flagged by `flags` are excluded from both polarizations
+    /// * `flags` - flags of the same shape as `x`, `true` for the samples to be excluded
+    pub fn integrate_stokes_masked(
+        &mut self,
+        x: ArrayView2<Complex<T>>,
+        y: ArrayView2<Complex<T>>,
+        flags: ArrayView2<bool>,
+    ) -> Vec<IntegratedSpectrum<T>> {
+        assert_eq!(self.kind, SpectrumKind::Stokes);
+        self.integrate(&[x.view(), y.view()], Some(flags))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cspfb::tests::white_noise;
+
+    #[test]
+    fn power_test() {
+        let nch = 8;
+        let ntime = 100;
+        let x = Array2::from_shape_vec((nch, ntime), white_noise(nch * ntime, 5)).unwrap();
+        let mut spec = Spectrometer::<f64>::new(SpectrumKind::Power, nch, 30, 2.0);
+        // the partial integrations carry over between the calls
+        let spectra: Vec<_> = [0, 7, 45, 46, 100]
+            .windows(2)
+            .flat_map(|w| spec.integrate_power(x.slice(s![.., w[0]..w[1]])))
+            .collect();
+        assert_eq!(spectra.len(), 3);
+        spectra.iter().enumerate().for_each(|(i, sp)| {
+            assert_eq!(sp.first_frame, i * 30);
+            assert_eq!(sp.time, (i * 30 + 15) as f64 / 2.0);
+            assert!(sp.nvalid.iter().all(|&n| n == 30));
+            (0..nch).for_each(|ch| {
+                let expected: f64 = x
+                    .slice(s![ch, i * 30..(i + 1) * 30])
+                    .iter()
+                    .map(|x| x.norm_sqr())
+                    .sum();
+                assert!((sp.data[[0, ch]] - expected).abs() < 1e-12);
+            });
+        });
+    }
+
+    #[test]
+    fn masked_test() {
+        let nch = 8;
+        let ntime = 60;
+        let x = Array2::from_shape_vec((nch, ntime), white_noise(nch * ntime, 7)).unwrap();
+        let flags = Array2::from_shape_fn((nch, ntime), |(ch, t)| ch == 2 || (ch + t) % 7 == 0);
+        let mut spec = Spectrometer::<f64>::new(SpectrumKind::Power, nch, 20, 1.0);
+        let spectra: Vec<_> = [0, 13, 60]
+            .windows(2)
+            .flat_map(|w| {
+                spec.integrate_power_masked(
+                    x.slice(s![.., w[0]..w[1]]),
+                    flags.slice(s![.., w[0]..w[1]]),
+                )
+            })
+            .collect();
+        assert_eq!(spectra.len(), 3);
+        spectra.iter().enumerate().for_each(|(i, sp)| {
+            (0..nch).for_each(|ch| {
+                let valid: Vec<_> = (i * 20..(i + 1) * 20)
+                    .filter(|&t| !flags[[ch, t]])
+                    .collect();
+                let expected: f64 = valid.iter().map(|&t| x[[ch, t]].norm_sqr()).sum();
+                assert_eq!(sp.nvalid[ch], valid.len());
+                assert!((sp.data[[0, ch]] - expected).abs() < 1e-12);
+            });
+        });
+        assert_eq!(spectra[0].nvalid[2], 0);
+    }
+
+    #[test]
+    fn stokes_test() {
+        let nch = 4;
+        let ntime = 20;
+        let x = Array2::from_shape_vec((nch, ntime), white_noise(nch * ntime, 6)).unwrap();
+        let power: Vec<f64> = x
+            .outer_iter()
+            .map(|r| r.iter().map(|x| x.norm_sqr()).sum())
+            .collect();
+        let check = |y: Array2<Complex<f64>>, expected: &dyn Fn(f64) -> [f64; 4]| {
+            let mut spec = Spectrometer::<f64>::new(SpectrumKind::Stokes, nch, ntime, 1.0);
+            let spectra = spec.integrate_stokes(x.view(), y.view());
+            assert_eq!(spectra.len(), 1);
+            (0..nch).for_each(|ch| {
+                let e = expected(power[ch]);
+                (0..4).for_each(|k| assert!((spectra[0].data[[k, ch]] - e[k]).abs() < 1e-12));
+            })
+        };
+        // only X
+        check(Array2::zeros((nch, ntime)), &|p| [p, p, 0.0, 0.0]);
+        // linearly polarized at 45 deg
+        check(x.clone(), &|p| [2.0 * p, 0.0, 2.0 * p, 0.0]);
+        // circularly polarized
+        check(x.mapv(|x| x * Complex::new(0.0, 1.0)), &|p| {
+            [2.0 * p, 0.0, 0.0, 2.0 * p]
+        });
+    }
+}
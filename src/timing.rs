@@ -0,0 +1,157 @@
+//! Tracking of the input sample that each output sample (or frame) of a streaming processor corresponds to,
+//! through the buffering, the decimation or interpolation, and the intrinsic delay of the filters
+
+use serde::{Deserialize, Serialize};
+
+/// Timing of the output of a streaming processor, e.g., returned by [`crate::channelizer::Channelizer::timing`].
+///
+/// Output `i` corresponds to the input sample at the position `offset+i*step`, counted from the first input sample
+/// ever fed to the processor, i.e., the center of the impulse response of output `i`.
+/// The position is fractional if the intrinsic delay of the filter is not an integer,
+/// and is negative for the outputs that only depend on the zero initial state
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Timing {
+    /// index of the next output, i.e., the number of outputs that have been produced
+    pub next_output: usize,
+    /// position of output `0`, in the unit of input samples, including the intrinsic delay
+    pub offset: f64,
+    /// number of input samples per output, e.g., the hop length of a filter bank, which is less than 1 for an up sampler
+    pub step: f64,
+}
+
+impl Timing {
+    /// timing of a filter bank, output frame `j` of which is filtered from the input samples ending at `(j+1)*hop-1`
+    /// by a linear phase prototype filter of `filter_len` taps, so that its position is `(j+1)*hop-1-(filter_len-1)/2`
+    /// * `nframes` - number of frames that have been produced
+    /// * `hop` - number of input samples between successive frames
+    /// * `filter_len` - length of the prototype filter
+    pub fn frames(nframes: usize, hop: usize, filter_len: usize) -> Self {
+        Self {
+            next_output: nframes,
+            offset: hop as f64 - 1.0 - (filter_len as f64 - 1.0) / 2.0,
+            step: hop as f64,
+        }
+    }
+
+    /// position of output `i`, in the unit of input samples
+    pub fn position(&self, i: usize) -> f64 {
+        self.offset + i as f64 * self.step
+    }
+
+    /// position of the first output of the next call, in the unit of input samples
+    pub fn next_position(&self) -> f64 {
+        self.position(self.next_output)
+    }
+
+    /// time of output `i`, in seconds
+    /// * `sample_rate` - sampling rate of the input
+    pub fn time(&self, i: usize, sample_rate: f64) -> f64 {
+        self.position(i) / sample_rate
+    }
+
+    /// sampling rate of the output
+    /// * `sample_rate` - sampling rate of the input
+    pub fn output_rate(&self, sample_rate: f64) -> f64 {
+        sample_rate / self.step
+    }
+
+    /// timing of a processor fed with the output of this one, in the unit of the input samples of this one
+    /// * `next` - timing of the following processor, in the unit of its own input, i.e., the output of this one
+    /// ```
+    /// extern crate rsdsp;
+    /// use rsdsp::timing::Timing;
+    ///
+    /// let coarse=Timing::frames(0, 8, 64);
+    /// let fine=Timing::frames(3, 16, 128);
+    /// let t=coarse.then(&fine);
+    /// assert_eq!(t.next_output, 3);
+    /// assert_eq!(t.step, 128.0);
+    /// // the position of a fine frame is that of the coarse frame at its position
+    /// assert_eq!(t.position(3), coarse.offset+fine.position(3)*coarse.step);
+    /// ```
+    pub fn then(&self, next: &Timing) -> Timing {
+        Timing {
+            next_output: next.next_output,
+            offset: self.offset + next.offset * self.step,
+            step: self.step * next.step,
+        }
+    }
+}
+
+/// Group delay of a streaming processor, i.e., how long an input sample takes to appear at the output,
+/// which is the number of input samples that have to be fed after it until the output corresponding to it is produced.
+///
+/// The filters are assumed to be of linear phase, so that the group delay is the same for all frequencies
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GroupDelay {
+    /// group delay in the unit of input samples
+    pub input: f64,
+    /// group delay in the unit of output samples
+    pub output: f64,
+}
+
+impl GroupDelay {
+    /// constructor
+    /// * `input` - group delay in the unit of input samples
+    /// * `step` - number of input samples per output, see [`Timing::step`]
+    pub fn new(input: f64, step: f64) -> Self {
+        Self {
+            input,
+            output: input / step,
+        }
+    }
+
+    /// group delay of a linear phase FIR filter of `filter_len` taps, i.e., `(filter_len-1)/2` input samples
+    /// * `filter_len` - length of the filter
+    /// * `step` - number of input samples per output
+    pub fn fir(filter_len: usize, step: f64) -> Self {
+        Self::new((filter_len as f64 - 1.0) / 2.0, step)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::{down_sample::DownSampler, filter::Filter, up_sample::UpSampler, windowed_fir};
+
+    /// an impulse at `n0`
+    fn impulse(n: usize, n0: usize) -> Vec<f64> {
+        let mut x = vec![0.0; n];
+        x[n0] = 1.0;
+        x
+    }
+
+    /// index of the sample with the max magnitude
+    pub(crate) fn peak(y: &[f64]) -> usize {
+        (0..y.len())
+            .reduce(|a, b| if y[b].abs() > y[a].abs() { b } else { a })
+            .unwrap()
+    }
+
+    #[test]
+    fn group_delay_test() {
+        let n0 = 100;
+        let x = impulse(400, n0);
+
+        let mut filter = Filter::<f64, f64>::new(windowed_fir::coeff(33, 0.25));
+        let gd = filter.group_delay();
+        let y: Vec<_> = x.chunks(70).flat_map(|x1| filter.filter(x1)).collect();
+        assert_eq!(peak(&y) as f64, n0 as f64 + gd.input);
+
+        // output `j` is produced by `j*ratio+1` input samples
+        let ratio = 4;
+        let mut ds = DownSampler::<f64, f64>::new(33, ratio);
+        let gd = ds.group_delay();
+        assert_eq!(gd.output * ratio as f64, gd.input);
+        let y: Vec<_> = x.chunks(70).flat_map(|x1| ds.downsample(x1)).collect();
+        let diff = (peak(&y) * ratio) as f64 - n0 as f64 - gd.input;
+        assert!(diff.abs() <= ratio as f64 / 2.0, "{diff}");
+
+        // output `m` is produced by `m/ratio+1` input samples
+        let mut us = UpSampler::<f64, f64>::new(8, ratio);
+        let gd = us.group_delay();
+        assert_eq!(gd.input * ratio as f64, gd.output);
+        let y: Vec<_> = x.chunks(70).flat_map(|x1| us.up_sample(x1)).collect();
+        let diff = peak(&y) as f64 - (n0 * ratio) as f64 - gd.output;
+        assert!(diff.abs() <= 0.5, "{diff}");
+    }
+}
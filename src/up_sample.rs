@@ -10,12 +10,17 @@ use std::{
 
 use ndarray::{s, Array1, Array2, Axis};
 
-use crate::windowed_fir::coeff;
+use crate::{
+    timing::{GroupDelay, Timing},
+    windowed_fir::coeff,
+};
 
 pub struct UpSampler<U, T> {
     pub coeffs: Array2<T>,
     pub init_state: Vec<U>,
     pub up_sample_ratio: usize,
+    /// number of samples that have been output
+    pub noutput: usize,
 }
 
 impl<U, T> UpSampler<U, T>
@@ -52,9 +57,39 @@ where
             coeffs,
             init_state,
             up_sample_ratio,
+            noutput: 0,
+        }
+    }
+
+    /// timing of the output, i.e., output `m` is at `(m-(tap-1)/2)/up_sample_ratio` input samples,
+    /// where `tap` is the length of the filter, assuming it is of linear phase
+    /// ```
+    /// extern crate rsdsp;
+    /// use rsdsp::up_sample::UpSampler;
+    ///
+    /// let mut us=UpSampler::<f64, f64>::new(8, 4);
+    /// let y=us.up_sample(&vec![1.0; 10]);
+    /// assert_eq!(us.timing().next_output, y.len());
+    /// assert_eq!(us.timing().step, 0.25);
+    /// assert_eq!(us.timing().position(0), -31.0/8.0);
+    /// ```
+    pub fn timing(&self) -> Timing {
+        let ratio = self.up_sample_ratio as f64;
+        let tap = self.coeffs.len() as f64;
+        Timing {
+            next_output: self.noutput,
+            offset: -(tap - 1.0) / 2.0 / ratio,
+            step: 1.0 / ratio,
         }
     }
 
+    /// group delay, i.e., `(tap-1)/2` output samples, where `tap` is the length of the filter
+    pub fn group_delay(&self) -> GroupDelay {
+        // the filter works at the output sampling rate
+        let ratio = self.up_sample_ratio as f64;
+        GroupDelay::new((self.coeffs.len() as f64 - 1.0) / 2.0 / ratio, 1.0 / ratio)
+    }
+
     pub fn up_sample(&mut self, input: &[U]) -> Vec<U> {
         let output_len = input.len();
         self.init_state.extend(input);
@@ -73,6 +108,7 @@ where
             });
 
         self.init_state = self.init_state[output_len..].to_vec();
+        self.noutput += output_len * self.up_sample_ratio;
         output
             .t()
             .as_standard_layout()
@@ -1,30 +1,360 @@
 #![allow(non_snake_case)]
 use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::HashMap,
     iter::Sum,
     ops::{Add, Index, Mul},
+    sync::Arc,
 };
 /*
 use fftw::{
     plan::{C2CPlan, C2CPlan32, C2CPlan64},
     types::{Flag, Sign},
 };*/
-use rustfft::{FftNum, FftPlanner};
+use rustfft::{Fft, FftNum, FftPlanner};
 
-use ndarray::{s, Array1, Array2, ArrayView1, ArrayView2};
+use rayon::prelude::*;
+
+use serde::{Deserialize, Serialize};
+
+use ndarray::{s, Array1, Array2, ArrayView1, ArrayView2, ArrayViewMut1, ArrayViewMut2};
 
 use num::{
     complex::Complex,
     traits::{Float, FloatConst, Num, NumAssign, NumCast},
 };
 
+thread_local! {
+    /// one planner for each float type, so that plans of the same length are reused
+    static PLANNERS: RefCell<HashMap<TypeId, Box<dyn Any>>> = RefCell::new(HashMap::new());
+}
+
+/// get an fft plan from the planner of the current thread, which caches all plans it has made
+pub fn cached_fft_plan<T>(len: usize, inverse: bool) -> Arc<dyn Fft<T>>
+where
+    T: FftNum,
+{
+    PLANNERS.with(|planners| {
+        let mut planners = planners.borrow_mut();
+        let planner = planners
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(FftPlanner::<T>::new()))
+            .downcast_mut::<FftPlanner<T>>()
+            .unwrap();
+        if inverse {
+            planner.plan_fft_inverse(len)
+        } else {
+            planner.plan_fft_forward(len)
+        }
+    })
+}
+
+/// serialized form of [`FftPlan`]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+struct FftPlanDesc {
+    len: usize,
+    inverse: bool,
+}
+
+/// An fft plan together with its scratch buffer, so that repeated transforms never re-plan or allocate.
+/// It is serialized as its length and direction, and planned again when deserialized.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "FftPlanDesc", into = "FftPlanDesc")]
+pub struct FftPlan<T>
+where
+    T: FftNum,
+{
+    fft: Arc<dyn Fft<T>>,
+    inverse: bool,
+    scratch: Vec<Complex<T>>,
+}
+
+impl<T> FftPlan<T>
+where
+    T: FftNum,
+{
+    /// forward fft of length `len`
+    pub fn forward(len: usize) -> Self {
+        Self::from(FftPlanDesc {
+            len,
+            inverse: false,
+        })
+    }
+
+    /// inverse fft of length `len`, not normalized
+    pub fn inverse(len: usize) -> Self {
+        Self::from(FftPlanDesc { len, inverse: true })
+    }
+
+    pub fn len(&self) -> usize {
+        self.fft.len()
+    }
+
+    /// plan again in the same direction if the length is not `len`,
+    /// e.g., the empty [`Default`] plan of a struct deserialized from a form saved without the plan
+    pub fn ensure_len(&mut self, len: usize) {
+        if self.len() != len {
+            *self = Self::from(FftPlanDesc {
+                len,
+                inverse: self.inverse,
+            });
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fft.len() == 0
+    }
+
+    /// transform `buffer` in place, the length of which should be a multiple of [`Self::len`],
+    /// each chunk being transformed separately
+    pub fn process(&mut self, buffer: &mut [Complex<T>]) {
+        self.fft.process_with_scratch(buffer, &mut self.scratch);
+    }
+
+    /// transform `buffer` in place with an external scratch buffer of at least [`Self::scratch_len`] elements,
+    /// so that it can be shared among threads
+    pub fn process_with_scratch(&self, buffer: &mut [Complex<T>], scratch: &mut [Complex<T>]) {
+        self.fft.process_with_scratch(buffer, scratch);
+    }
+
+    /// length of the scratch buffer required by [`Self::process_with_scratch`]
+    pub fn scratch_len(&self) -> usize {
+        self.scratch.len()
+    }
+
+    /// parallel version of [`Self::process`]
+    pub fn process_par(&self, buffer: &mut [Complex<T>]) {
+        let scratch_len = self.scratch.len();
+        buffer.par_chunks_mut(self.len()).for_each_init(
+            || vec![Complex::<T>::new(T::zero(), T::zero()); scratch_len],
+            |scratch, x| self.fft.process_with_scratch(x, scratch),
+        );
+    }
+}
+
+impl<T> Default for FftPlan<T>
+where
+    T: FftNum,
+{
+    /// an empty forward plan, see [`FftPlan::ensure_len`]
+    fn default() -> Self {
+        Self::forward(0)
+    }
+}
+
+impl<T> From<FftPlanDesc> for FftPlan<T>
+where
+    T: FftNum,
+{
+    fn from(desc: FftPlanDesc) -> Self {
+        let fft = cached_fft_plan(desc.len, desc.inverse);
+        let scratch = vec![Complex::<T>::new(T::zero(), T::zero()); fft.get_inplace_scratch_len()];
+        Self {
+            fft,
+            inverse: desc.inverse,
+            scratch,
+        }
+    }
+}
+
+impl<T> From<FftPlan<T>> for FftPlanDesc
+where
+    T: FftNum,
+{
+    fn from(plan: FftPlan<T>) -> Self {
+        Self {
+            len: plan.len(),
+            inverse: plan.inverse,
+        }
+    }
+}
+
+/// serialized form of [`RealFftPlan`]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+struct RealFftPlanDesc {
+    len: usize,
+}
+
+/// A forward fft of real input with even length `len`, which returns only the `len/2+1` non-negative frequency bins.
+/// The input is packed into a complex array of length `len/2`, so that it costs about half of a complex fft.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "RealFftPlanDesc", into = "RealFftPlanDesc")]
+pub struct RealFftPlan<T>
+where
+    T: FftNum + Float + FloatConst,
+{
+    fft: FftPlan<T>,
+    /// `exp(-2*pi*i*k/len)` for `k` in `0..=len/2`
+    twiddle: Vec<Complex<T>>,
+    /// packed input
+    buffer: Vec<Complex<T>>,
+}
+
+impl<T> RealFftPlan<T>
+where
+    T: FftNum + Float + FloatConst,
+{
+    pub fn new(len: usize) -> Self {
+        Self::try_from(RealFftPlanDesc { len }).unwrap()
+    }
+
+    /// length of the real input
+    pub fn len(&self) -> usize {
+        self.fft.len() * 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fft.is_empty()
+    }
+
+    /// number of output bins, i.e., `len/2+1`
+    pub fn output_len(&self) -> usize {
+        self.fft.len() + 1
+    }
+
+    /// transform each row of `input`, which has `len` columns, and write the result to the corresponding row of `output`,
+    /// which has `len/2+1` columns
+    pub fn process(&mut self, input: ArrayView2<T>, mut output: ArrayViewMut2<Complex<T>>) {
+        let m = self.fft.len();
+        assert_eq!(input.ncols(), 2 * m);
+        assert_eq!(output.ncols(), m + 1);
+        assert_eq!(input.nrows(), output.nrows());
+        self.buffer
+            .resize(m, Complex::<T>::new(T::zero(), T::zero()));
+        input
+            .outer_iter()
+            .zip(output.outer_iter_mut())
+            .for_each(|(x, mut y)| {
+                pack_real(x, &mut self.buffer);
+                self.fft.process(&mut self.buffer);
+                split_real(&self.buffer, &self.twiddle, y.view_mut());
+            });
+    }
+
+    /// parallel version of [`Self::process`]
+    pub fn process_par(&mut self, input: ArrayView2<T>, mut output: ArrayViewMut2<Complex<T>>) {
+        let m = self.fft.len();
+        assert_eq!(input.ncols(), 2 * m);
+        assert_eq!(output.ncols(), m + 1);
+        assert_eq!(input.nrows(), output.nrows());
+        let fft = &self.fft;
+        let twiddle = &self.twiddle;
+        input
+            .outer_iter()
+            .into_par_iter()
+            .zip(output.outer_iter_mut().into_par_iter())
+            .for_each_init(
+                || {
+                    (
+                        vec![Complex::<T>::new(T::zero(), T::zero()); m],
+                        vec![Complex::<T>::new(T::zero(), T::zero()); fft.scratch_len()],
+                    )
+                },
+                |(buffer, scratch), (x, mut y)| {
+                    pack_real(x, buffer);
+                    fft.process_with_scratch(buffer, scratch);
+                    split_real(buffer, twiddle, y.view_mut());
+                },
+            );
+    }
+}
+
+/// pack real `x` into complex `z`, with `z[n]=x[2n]+i*x[2n+1]`
+fn pack_real<T>(x: ArrayView1<T>, z: &mut [Complex<T>])
+where
+    T: Copy,
+{
+    z.iter_mut()
+        .zip(x.iter().step_by(2).zip(x.iter().skip(1).step_by(2)))
+        .for_each(|(z1, (&re, &im))| *z1 = Complex::new(re, im));
+}
+
+/// recover the spectrum of the real signal from the fft `z` of the packed one
+fn split_real<T>(z: &[Complex<T>], twiddle: &[Complex<T>], mut y: ArrayViewMut1<Complex<T>>)
+where
+    T: Float,
+{
+    let m = z.len();
+    let half = T::from(0.5).unwrap();
+    y.iter_mut()
+        .zip(twiddle)
+        .enumerate()
+        .for_each(|(k, (y1, &w))| {
+            let a = z[k % m];
+            let b = z[(m - k % m) % m].conj();
+            let even = (a + b) * half;
+            let odd = (a - b) * Complex::new(T::zero(), -half);
+            *y1 = even + w * odd;
+        });
+}
+
+impl<T> TryFrom<RealFftPlanDesc> for RealFftPlan<T>
+where
+    T: FftNum + Float + FloatConst,
+{
+    type Error = String;
+
+    fn try_from(desc: RealFftPlanDesc) -> Result<Self, Self::Error> {
+        if !desc.len.is_multiple_of(2) {
+            return Err(format!("length {} of a real fft should be even", desc.len));
+        }
+        let m = desc.len / 2;
+        let twiddle = (0..=m)
+            .map(|k| {
+                let phi = -T::PI() * T::from(2 * k).unwrap() / T::from(desc.len).unwrap();
+                Complex::new(phi.cos(), phi.sin())
+            })
+            .collect();
+        Ok(Self {
+            fft: FftPlan::forward(m),
+            twiddle,
+            buffer: vec![Complex::<T>::new(T::zero(), T::zero()); m],
+        })
+    }
+}
+
+impl<T> From<RealFftPlan<T>> for RealFftPlanDesc
+where
+    T: FftNum + Float + FloatConst,
+{
+    fn from(plan: RealFftPlan<T>) -> Self {
+        Self {
+            len: plan.fft.len() * 2,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for RealFftPlan<T>
+where
+    T: FftNum + Float + FloatConst,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RealFftPlan")
+            .field("len", &(self.fft.len() * 2))
+            .finish()
+    }
+}
+
+impl<T> std::fmt::Debug for FftPlan<T>
+where
+    T: FftNum,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FftPlan")
+            .field("len", &self.len())
+            .field("inverse", &self.inverse)
+            .finish()
+    }
+}
+
 pub fn fft<T>(in_data: &[Complex<T>]) -> Vec<Complex<T>>
 where
     T: Float + FloatConst + NumAssign + FftNum,
 {
     //let mut fft = CFft1D::<T>::with_len(in_data.len());
     let mut output = Vec::from(in_data);
-    let mut planner = FftPlanner::new();
-    let fft = planner.plan_fft_forward(in_data.len());
+    let fft = cached_fft_plan(in_data.len(), false);
     fft.process(&mut output);
     output
 }
@@ -34,8 +364,7 @@ where
     T: Float + FloatConst + NumAssign + FftNum,
 {
     let mut output = Vec::from(in_data);
-    let mut planner = FftPlanner::new();
-    let fft = planner.plan_fft_inverse(in_data.len());
+    let fft = cached_fft_plan(in_data.len(), true);
     fft.process(&mut output);
     let norm = T::from(in_data.len()).unwrap();
     output.iter_mut().for_each(|x| *x /= norm);
@@ -47,8 +376,7 @@ where
     T: Float + FloatConst + NumAssign + FftNum,
 {
     let mut output = Vec::from(in_data);
-    let mut planner = FftPlanner::new();
-    let fft = planner.plan_fft_inverse(in_data.len());
+    let fft = cached_fft_plan(in_data.len(), true);
     fft.process(&mut output);
     output
 }
@@ -58,7 +386,7 @@ where
     T: Copy,
 {
     let mut result = Vec::with_capacity(in_data.len());
-    let n = (in_data.len() + 1) / 2;
+    let n = in_data.len().div_ceil(2);
     //for i in n..in_data.len() {
     for item in in_data.iter().skip(n).cloned() {
         //result[i-n-1]=in_data[i];
@@ -76,7 +404,7 @@ pub fn fftshift2<T>(in_data: ArrayView2<T>) -> Array2<T>
 where
     T: Copy,
 {
-    assert!(in_data.shape()[0] % 2 == 0);
+    assert!(in_data.shape()[0].is_multiple_of(2));
     let n2 = in_data.shape()[0] / 2;
     let mut result =
         unsafe { Array2::uninit((in_data.shape()[0], in_data.shape()[1])).assume_init() };
@@ -127,7 +455,7 @@ impl<'a, 'b, T> ConcatedSlice<'a, 'b, T> {
     }
 }
 
-impl<T> Index<usize> for ConcatedSlice<'_, '_, T>{
+impl<T> Index<usize> for ConcatedSlice<'_, '_, T> {
     type Output = T;
     fn index(&self, idx: usize) -> &T {
         let b = self.old.len();
@@ -139,6 +467,23 @@ impl<T> Index<usize> for ConcatedSlice<'_, '_, T>{
     }
 }
 
+/// rebuild the full spectrum with `nch` rows from the non-negative frequency part of a real signal with `nch/2+1` rows,
+/// e.g., the output of [`crate::cspfb::RealAnalyzer`], by filling the negative frequencies with the complex conjugates
+pub fn add_neg_freq<T>(input: ArrayView2<Complex<T>>, nch: usize) -> Array2<Complex<T>>
+where
+    T: Num + Copy + std::ops::Neg<Output = T>,
+{
+    assert_eq!(input.nrows(), nch / 2 + 1);
+    let mut result = Array2::<Complex<T>>::zeros((nch, input.ncols()));
+    result.slice_mut(s![..=nch / 2, ..]).assign(&input);
+    (nch / 2 + 1..nch).for_each(|k| {
+        result
+            .row_mut(k)
+            .assign(&input.row(nch - k).map(|x| x.conj()));
+    });
+    result
+}
+
 pub fn add_neg_freq_no_dc<T>(input: Array2<Complex<T>>) -> Array2<Complex<T>>
 where
     T: Num + Copy + std::ops::Neg<Output = T>,
@@ -264,9 +609,8 @@ where
 
     let tailing_zeros = (0..(nfft - kernel.len())).map(|_| Complex::<T>::default());
     let mut kernel1: Vec<_> = kernel.iter().cloned().chain(tailing_zeros).collect();
-    let mut planner = FftPlanner::<T>::new();
-    let fft = planner.plan_fft_forward(nfft);
-    let ifft = planner.plan_fft_inverse(nfft);
+    let fft = cached_fft_plan::<T>(nfft, false);
+    let ifft = cached_fft_plan::<T>(nfft, true);
 
     fft.process(&mut state1);
     fft.process(&mut kernel1);
@@ -278,14 +622,14 @@ where
     signal.copy_from_slice(&state1[(tap - 1)..]);
 }
 
-pub fn polyphase_decomp<T>(coeff: &[T], nch: usize)->Array2<T>
+pub fn polyphase_decomp<T>(coeff: &[T], nch: usize) -> Array2<T>
 where
     T: Copy,
 {
     let tap = coeff.len() / nch;
     let coeff = ArrayView1::from(coeff);
     let coeff = coeff
-        .into_shape((tap, nch))
+        .into_shape_with_order((tap, nch))
         .unwrap()
         .t()
         .as_standard_layout()
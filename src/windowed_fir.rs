@@ -88,10 +88,9 @@ where
         .iter()
         .enumerate()
         .map(|(i, &x)| {
-            let f = (Complex::new(T::zero(), T::one())
-                * w
-                * T::from(i as isize - tap / 2).unwrap())
-            .exp();
+            let f =
+                (Complex::new(T::zero(), T::one()) * w * T::from(i as isize - tap / 2).unwrap())
+                    .exp();
             f * x
         })
         .collect()